@@ -182,7 +182,7 @@ pub async fn refresh_token() -> Result<RefreshTokenResponse, String> {
 
     // Check if the token was already refreshed by another thread while we waited
     if let Ok(Some(state)) = retrieve_auth_state().map_err(|e| e.to_string()) {
-        if !state.is_access_token_expired() {
+        if !state.is_access_token_expiring() {
             log::info!("[supabase_auth] Token already refreshed by concurrent request. Returning valid session.");
             return Ok(RefreshTokenResponse {
                 session: state.session.clone(),
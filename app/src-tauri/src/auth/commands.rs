@@ -1,8 +1,10 @@
 use crate::auth::types::{
     UserInfo, SupabaseUser, AuthError, AuthState, AuthErrorResponse, AuthErrorCode,
+    CredentialKind, StoredCredential,
 };
 use crate::auth::storage::{
-    retrieve_auth_state, clear_auth_state,
+    retrieve_auth_state, clear_auth_state, list_credentials, revoke_keyring_credential,
+    revoke_ends_session, SystemKeyring,
 };
 use crate::auth::auth_flow::{refresh_token, fetch_user_profile};
 use crate::auth::security::HTTP_CLIENT;
@@ -134,11 +136,18 @@ pub async fn get_access_token_command() -> Result<Option<String>, String> {
             let is_expired = state.is_access_token_expired();
             let current_token = state.session.access_token.clone();
             
-            if is_expired {
-                // Try to refresh
+            // Refresh slightly early so the token doesn't expire mid-request
+            if state.is_access_token_expiring() {
                 match refresh_token().await {
                     Ok(refreshed) => Ok(Some(refreshed.session.access_token)),
-                    Err(_) => {
+                    Err(e) => {
+                        // A rejected refresh token clears the stored session
+                        let session_cleared = matches!(retrieve_auth_state(), Ok(None));
+                        if !is_expired && !session_cleared {
+                            log::warn!("[supabase_auth] Early refresh failed, using current token: {}", e);
+                            return Ok(Some(current_token));
+                        }
+                        log::warn!("[supabase_auth] Token refresh failed, signing out: {}", e);
                         let _ = clear_auth_state();
                         Ok(None)
                     }
@@ -152,9 +161,46 @@ pub async fn get_access_token_command() -> Result<Option<String>, String> {
     }
 }
 
+/// Get the signed-in user's display name from the stored session, if any.
+/// The name is used for prompt personalization and is never logged.
+pub fn get_user_name() -> Option<String> {
+    let state = retrieve_auth_state().ok().flatten()?;
+    UserInfo::from(&state.session.user)
+        .full_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
 #[tauri::command]
 pub async fn emit_auth_changed(app_handle: AppHandle) -> Result<(), String> {
     app_handle
         .emit("auth_changed", ())
         .map_err(|e| format!("Failed to emit auth_changed event: {}", e))
-}
\ No newline at end of file
+}
+
+/// List the credentials stored on this device, without their secrets
+#[tauri::command]
+pub async fn list_stored_credentials() -> Result<Vec<StoredCredential>, String> {
+    let auth_state = retrieve_auth_state()
+        .map_err(|e| AuthErrorResponse::storage_error(format!("Failed to read auth state: {}", e)).to_string())?;
+    Ok(list_credentials(&SystemKeyring, auth_state.as_ref()))
+}
+
+/// Revoke a single stored credential
+#[tauri::command]
+pub async fn revoke_credential(app_handle: AppHandle, kind: CredentialKind) -> Result<(), String> {
+    let auth_state = retrieve_auth_state()
+        .map_err(|e| AuthErrorResponse::storage_error(format!("Failed to read auth state: {}", e)).to_string())?;
+    let credentials = list_credentials(&SystemKeyring, auth_state.as_ref());
+
+    revoke_keyring_credential(&SystemKeyring, kind)?;
+
+    // The session lives in the store, and without the encryption key its tokens can't be read
+    if revoke_ends_session(kind, &credentials) {
+        clear_auth_state()
+            .map_err(|e| AuthErrorResponse::storage_error(format!("Failed to clear auth state: {}", e)).to_string())?;
+    }
+
+    log::info!("[auth_commands] Revoked stored credential {:?}", kind);
+    emit_auth_changed(app_handle).await
+}
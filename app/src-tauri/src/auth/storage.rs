@@ -1,4 +1,4 @@
-use crate::auth::types::{StoredAuthState, Session};
+use crate::auth::types::{CredentialKind, StoredAuthState, StoredCredential, Session};
 use crate::constants::{AUTH_KEY, STORE_PATH, KEYRING_ENCRYPTION_KEY, KEYRING_AUTH_KEY, KEYRING_SERVICE};
 use keyring::Entry;
 use serde::{Deserialize, Serialize};
@@ -213,4 +213,136 @@ pub fn clear_auth_state() -> Result<(), Box<dyn std::error::Error>> {
     
     log::info!("[auth_storage] Auth state cleared successfully");
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Minimal keyring interface so credential bookkeeping can run without the OS keychain
+pub trait KeyringBackend {
+    fn exists(&self, key: &str) -> bool;
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// The platform keyring under the app's service name
+pub struct SystemKeyring;
+
+impl KeyringBackend for SystemKeyring {
+    fn exists(&self, key: &str) -> bool {
+        Entry::new(KEYRING_SERVICE, key)
+            .and_then(|entry| entry.get_password())
+            .is_ok()
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        let entry = Entry::new(KEYRING_SERVICE, key)
+            .map_err(|e| format!("Failed to open keyring entry: {}", e))?;
+        match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to delete keyring entry: {}", e)),
+        }
+    }
+}
+
+/// List which credentials exist, given the keyring and the decoded auth state
+pub fn list_credentials(
+    keyring: &dyn KeyringBackend,
+    auth_state: Option<&StoredAuthState>,
+) -> Vec<StoredCredential> {
+    let mut credentials = Vec::new();
+
+    if auth_state.is_some() || keyring.exists(KEYRING_AUTH_KEY) {
+        credentials.push(StoredCredential {
+            kind: CredentialKind::SupabaseSession,
+            expires_at: auth_state.and_then(|s| s.session.expires_at),
+        });
+    }
+    if keyring.exists(KEYRING_ENCRYPTION_KEY) {
+        credentials.push(StoredCredential {
+            kind: CredentialKind::StorageEncryptionKey,
+            expires_at: None,
+        });
+    }
+
+    credentials
+}
+
+/// Delete the keyring entries backing a credential kind
+pub fn revoke_keyring_credential(
+    keyring: &dyn KeyringBackend,
+    kind: CredentialKind,
+) -> Result<(), String> {
+    match kind {
+        CredentialKind::SupabaseSession => keyring.delete(KEYRING_AUTH_KEY),
+        CredentialKind::StorageEncryptionKey => keyring.delete(KEYRING_ENCRYPTION_KEY),
+    }
+}
+
+/// Whether revoking `kind` ends the current session. The session's tokens are stored
+/// encrypted, so both the session and the key it was encrypted with back it, but only
+/// when they are actually stored.
+pub fn revoke_ends_session(kind: CredentialKind, credentials: &[StoredCredential]) -> bool {
+    let stored = |kind: CredentialKind| credentials.iter().any(|c| c.kind == kind);
+    stored(CredentialKind::SupabaseSession) && stored(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+
+    struct MockKeyring(RefCell<HashSet<String>>);
+
+    impl KeyringBackend for MockKeyring {
+        fn exists(&self, key: &str) -> bool {
+            self.0.borrow().contains(key)
+        }
+
+        fn delete(&self, key: &str) -> Result<(), String> {
+            self.0.borrow_mut().remove(key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_list_and_revoke_credentials() {
+        let keyring = MockKeyring(RefCell::new(
+            [KEYRING_AUTH_KEY, KEYRING_ENCRYPTION_KEY]
+                .iter()
+                .map(|k| k.to_string())
+                .collect(),
+        ));
+
+        let kinds: Vec<CredentialKind> = list_credentials(&keyring, None)
+            .into_iter()
+            .map(|c| c.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![CredentialKind::SupabaseSession, CredentialKind::StorageEncryptionKey]
+        );
+
+        revoke_keyring_credential(&keyring, CredentialKind::SupabaseSession).unwrap();
+        let remaining = list_credentials(&keyring, None);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].kind, CredentialKind::StorageEncryptionKey);
+    }
+
+    #[test]
+    fn test_revoke_only_ends_a_stored_session() {
+        let credential = |kind| StoredCredential { kind, expires_at: None };
+        let signed_in = [
+            credential(CredentialKind::SupabaseSession),
+            credential(CredentialKind::StorageEncryptionKey),
+        ];
+        assert!(revoke_ends_session(CredentialKind::SupabaseSession, &signed_in));
+        assert!(revoke_ends_session(CredentialKind::StorageEncryptionKey, &signed_in));
+
+        // A leftover encryption key with nobody signed in
+        let signed_out = [credential(CredentialKind::StorageEncryptionKey)];
+        assert!(!revoke_ends_session(CredentialKind::StorageEncryptionKey, &signed_out));
+        assert!(!revoke_ends_session(CredentialKind::SupabaseSession, &signed_out));
+
+        // Revoking a key that isn't stored leaves the session alone
+        let session_only = [credential(CredentialKind::SupabaseSession)];
+        assert!(!revoke_ends_session(CredentialKind::StorageEncryptionKey, &session_only));
+    }
+}
@@ -212,29 +212,29 @@ impl StoredAuthState {
     
     /// Check if the access token has expired
     pub fn is_access_token_expired(&self) -> bool {
-        if let Some(expires_at) = self.session.expires_at {
-            let now = chrono::Utc::now().timestamp();
-            now >= expires_at
-        } else {
-            // Fall back to expires_in calculation
-            let now = chrono::Utc::now().timestamp();
-            let expires_at = self.stored_at + self.session.expires_in;
-            now >= expires_at
-        }
+        self.expires_within(0)
+    }
+    
+    /// Check if the access token is about to expire and should not be sent (within 60 seconds of expiry)
+    pub fn is_access_token_expiring(&self) -> bool {
+        const EXPIRY_MARGIN_SECS: i64 = 60;
+        self.expires_within(EXPIRY_MARGIN_SECS)
     }
     
     /// Check if token needs refresh (within 5 minutes of expiry)
     pub fn needs_refresh(&self) -> bool {
         const REFRESH_THRESHOLD_SECS: i64 = 300; // 5 minutes
-        
-        if let Some(expires_at) = self.session.expires_at {
-            let now = chrono::Utc::now().timestamp();
-            now >= (expires_at - REFRESH_THRESHOLD_SECS)
-        } else {
-            let now = chrono::Utc::now().timestamp();
-            let expires_at = self.stored_at + self.session.expires_in;
-            now >= (expires_at - REFRESH_THRESHOLD_SECS)
-        }
+        self.expires_within(REFRESH_THRESHOLD_SECS)
+    }
+    
+    fn expires_within(&self, margin_secs: i64) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        // Fall back to expires_in calculation
+        let expires_at = self
+            .session
+            .expires_at
+            .unwrap_or(self.stored_at + self.session.expires_in);
+        now >= (expires_at - margin_secs)
     }
 }
 
@@ -418,3 +418,56 @@ pub struct AuthState {
     pub needs_refresh: bool,
     pub expires_at: Option<i64>,
 }
+
+/// Kinds of credentials the app may hold on this device
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "auth.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialKind {
+    /// Supabase session tokens (encrypted in the store)
+    SupabaseSession,
+    /// Keyring key used to encrypt stored tokens
+    StorageEncryptionKey,
+}
+
+/// A stored credential, described without exposing any secret
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct StoredCredential {
+    pub kind: CredentialKind,
+    /// Expiry as a Unix timestamp, when known
+    pub expires_at: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_expiring_in(secs: i64) -> StoredAuthState {
+        let session: Session = serde_json::from_value(serde_json::json!({
+            "access_token": "access",
+            "token_type": "bearer",
+            "expires_in": 3600,
+            "expires_at": chrono::Utc::now().timestamp() + secs,
+            "refresh_token": "refresh",
+            "user": { "id": "user-1" }
+        }))
+        .unwrap();
+        StoredAuthState::new(session)
+    }
+
+    #[test]
+    fn test_token_is_refreshed_within_a_minute_of_expiry() {
+        let fresh = state_expiring_in(3600);
+        assert!(!fresh.is_access_token_expiring());
+        assert!(!fresh.is_access_token_expired());
+
+        let expiring = state_expiring_in(30);
+        assert!(expiring.is_access_token_expiring());
+        assert!(!expiring.is_access_token_expired());
+
+        let expired = state_expiring_in(-10);
+        assert!(expired.is_access_token_expiring());
+        assert!(expired.is_access_token_expired());
+    }
+}
@@ -46,14 +46,64 @@ pub const SUPABASE_ANON_KEY: &str = "sb_publishable_LxTjFAXSpnDlB9d_8pYWAg_vs87q
 
 // Cloudflare configuration
 pub const CLOUDFLARE_COMPLETIONS_WORKER_URL: &str = "https://llm-completions.lukesutor.workers.dev/";
+pub const COMPARE_MODEL_TIMEOUT: Duration = Duration::from_secs(120);
+pub const CLOUD_BREAKER_FAILURE_THRESHOLD: u8 = 3;
+pub const CLOUD_BREAKER_COOLDOWN: Duration = Duration::from_secs(60);
+pub const LLM_DEBUG_LOG_LIMIT: u32 = 50;
+pub const STREAM_SAVE_INTERVAL: Duration = Duration::from_millis(200);
+
+// Ollama configuration
+pub const OLLAMA_DEFAULT_BASE_URL: &str = "http://localhost:11434";
+pub const OLLAMA_DEFAULT_MODEL: &str = "llama3.2";
+
+// Memory search fetches this many times the limit by similarity, then reranks
+pub const MEMORY_RERANK_CANDIDATE_FACTOR: u32 = 3;
+
+// How long a query waits for another connection's lock before failing
+pub const DB_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Conversation resume summaries
+pub const RESUME_SUMMARY_MIN_MESSAGES: i32 = 20;
+pub const RESUME_SUMMARY_MAX_MESSAGE_CHARS: usize = 1000;
 
 // Server setup
 pub const MAX_PORT: u16 = 9999;
 pub const MIN_PORT: u16 = 8000;
 pub const MAX_PORT_ATTEMPTS: u8 = 20;
 pub const HEALTH_CHECK_ENDPOINT: &str = "/health";
-pub const MAX_HEALTH_CHECK_RETRIES: u8 = 30;
-pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+pub const SLOTS_ENDPOINT: &str = "/slots";
+pub const METRICS_ENDPOINT: &str = "/metrics";
+// Startup polls quickly since a server that exits is caught straight away. 150 checks
+// 2s apart gives a large model five minutes to load.
+pub const MAX_HEALTH_CHECK_RETRIES: u8 = 150;
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+pub const MODEL_LOADING_RETRIES: u8 = 5;
+pub const MODEL_LOADING_INTERVAL: Duration = Duration::from_secs(1);
+pub const REQUEST_RETRY_ATTEMPTS: u8 = 3;
+pub const REQUEST_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
+pub const WATCHDOG_INTERVAL: Duration = Duration::from_secs(30);
+pub const WATCHDOG_FAILURE_THRESHOLD: u8 = 3;
+pub const WATCHDOG_MAX_RESTART_ATTEMPTS: u8 = 3;
+pub const WATCHDOG_RESTART_BASE_DELAY: Duration = Duration::from_secs(5);
+pub const MIN_CTX_SIZE: u32 = 512;
+pub const MODEL_SWITCH_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+pub const MAX_PARALLEL_SEQUENCES: u32 = 16;
+pub const KV_CACHE_QUANTS: [&str; 9] = [
+  "f32", "f16", "bf16", "q8_0", "q4_0", "q4_1", "iq4_nl", "q5_0", "q5_1",
+];
+/// Flags Ambient sets itself; overriding them would leave the app unable to reach the server
+pub const RESERVED_SERVER_FLAGS: [&str; 9] = [
+  "--port",
+  "--host",
+  "--api-key",
+  "--api-key-file",
+  "-m",
+  "--model",
+  "-mm",
+  "--mmproj",
+  "--offline",
+];
 
 // HUD information
 pub const HUD_WINDOW_LABEL: &str = "main";
@@ -67,6 +117,7 @@ pub const COMPUTER_USE_WINDOW_LABEL: &str = "computer-use";
 pub const COMPUTER_USE_PATH: &str = "/computer-use";
 pub const MARGIN_LEFT: u32 = 50;
 pub const MARGIN_BOTTOM: u32 = 20;
+pub const COMPUTER_USE_REPEAT_LIMIT: usize = 3;
 
 // Cost, water, and energy estimates per token
 pub const COST_PER_TOKEN: f64 = 0.000004375; // USD/token
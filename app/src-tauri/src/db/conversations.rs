@@ -1,16 +1,19 @@
-use crate::db::core::DbState;
-use crate::events::types::AttachmentData;
+use crate::db::core::{DbError, DbState};
+use crate::db::pagination::Paginated;
+use crate::events::{emitter::emit, types::{AttachmentData, MessagesDeletedEvent, MESSAGES_DELETED}};
 use crate::memory::types::MemoryEntry;
 use chrono::Utc;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 use ts_rs::TS;
 use uuid::Uuid;
 use rusqlite::Connection;
 use base64::{Engine as _, engine::general_purpose};
+use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, TS)]
+#[serde(rename_all = "lowercase")]
 #[ts(rename_all = "lowercase")]
 #[ts(export, export_to = "conversations.ts")]
 pub enum Role {
@@ -18,6 +21,8 @@ pub enum Role {
   User,
   Assistant,
   FunctionCall,
+  /// Marks a fresh context window, earlier messages are not sent to the model
+  ContextBoundary,
 }
 
 impl Role {
@@ -27,6 +32,7 @@ impl Role {
       Role::User => "user",
       Role::Assistant => "assistant",
       Role::FunctionCall => "functioncall",
+      Role::ContextBoundary => "contextboundary",
     }
   }
 
@@ -36,6 +42,7 @@ impl Role {
       "user" => Role::User,
       "assistant" => Role::Assistant,
       "functioncall" => Role::FunctionCall,
+      "contextboundary" => Role::ContextBoundary,
       _ => Role::User,
     }
   }
@@ -67,6 +74,15 @@ pub struct Message {
   pub memory: Option<MemoryEntry>,
 }
 
+/// A message matching a full-text search, with a highlighted excerpt
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "conversations.ts")]
+pub struct MessageSearchResult {
+  /// Attachments and memory are not loaded for search results
+  pub message: Message,
+  pub snippet: String,
+}
+
 /// Conversation structure
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "conversations.ts")]
@@ -77,6 +93,8 @@ pub struct Conversation {
   pub created_at: String,
   pub updated_at: String,
   pub message_count: i32,
+  pub is_pinned: bool,
+  pub is_archived: bool,
 }
 
 /// Generate a conversation name from the first message
@@ -102,15 +120,15 @@ pub async fn create_conversation(
   app_handle: AppHandle,
   name: Option<String>,
   conv_type: Option<String>,
-) -> Result<Conversation, String> {
+) -> Result<Conversation, DbError> {
   let state = app_handle.state::<DbState>();
   let conn_guard = state
     .0
     .lock()
-    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    .map_err(|_| DbError::poisoned())?;
   let conn = conn_guard
     .as_ref()
-    .ok_or("Database connection not available.".to_string())?;
+    .ok_or_else(DbError::unavailable)?;
 
   let conversation_id = Uuid::new_v4().to_string();
   let now = Utc::now();
@@ -124,6 +142,8 @@ pub async fn create_conversation(
     created_at: now.to_rfc3339(),
     updated_at: now.to_rfc3339(),
     message_count: 0,
+    is_pinned: false,
+    is_archived: false,
   };
 
   conn
@@ -139,7 +159,7 @@ pub async fn create_conversation(
         0
       ],
     )
-    .map_err(|e| format!("Failed to create conversation: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to create conversation", e))?;
 
   log::info!(
     "[conversations] Created conversation: {} ({})",
@@ -155,7 +175,7 @@ pub async fn add_message(
   conversation_id: String,
   role: String,
   content: String,
-) -> Result<Message, String> {
+) -> Result<Message, DbError> {
   add_message_with_id(app_handle, conversation_id, role, content, None).await
 }
 
@@ -165,15 +185,15 @@ pub async fn add_message_with_id(
   role: String,
   content: String,
   message_id: Option<String>,
-) -> Result<Message, String> {
+) -> Result<Message, DbError> {
   let state = app_handle.state::<DbState>();
   let conn_guard = state
     .0
     .lock()
-    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    .map_err(|_| DbError::poisoned())?;
   let conn = conn_guard
     .as_ref()
-    .ok_or("Database connection not available.".to_string())?;
+    .ok_or_else(DbError::unavailable)?;
 
   let message_id = message_id.unwrap_or_else(|| Uuid::new_v4().to_string());
   let now = Utc::now();
@@ -201,7 +221,7 @@ pub async fn add_message_with_id(
         now.to_rfc3339()
       ],
     )
-    .map_err(|e| format!("Failed to add message: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to add message", e))?;
 
   // Update conversation
   conn
@@ -209,7 +229,7 @@ pub async fn add_message_with_id(
       "UPDATE conversations SET message_count = message_count + 1, updated_at = ?1 WHERE id = ?2",
       params![now.to_rfc3339(), conversation_id],
     )
-    .map_err(|e| format!("Failed to update conversation: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to update conversation", e))?;
 
   // Auto-update conversation name if it's the first user message
   if Role::from_str(&role) == Role::User {
@@ -233,6 +253,406 @@ pub async fn add_message_with_id(
   Ok(message)
 }
 
+/// Write a message's content, creating the message on the first write.
+/// Used to save streaming responses as they arrive.
+pub async fn save_message_content(
+  app_handle: &AppHandle,
+  conversation_id: String,
+  message_id: String,
+  role: String,
+  content: String,
+) -> Result<(), String> {
+  let updated = {
+    let state = app_handle.state::<DbState>();
+    let conn_guard = state
+      .0
+      .lock()
+      .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    let conn = conn_guard
+      .as_ref()
+      .ok_or("Database connection not available.".to_string())?;
+    update_message_content(conn, &message_id, &content)?
+  };
+
+  if !updated {
+    add_message_with_id(app_handle, conversation_id, role, content, Some(message_id)).await?;
+  }
+  Ok(())
+}
+
+fn update_message_content(conn: &Connection, message_id: &str, content: &str) -> Result<bool, String> {
+  let rows = conn
+    .execute(
+      "UPDATE conversation_messages SET content = ?1 WHERE id = ?2",
+      params![content, message_id],
+    )
+    .map_err(|e| format!("Failed to update message: {}", e))?;
+  Ok(rows > 0)
+}
+
+/// Start a fresh context window. Earlier messages stay in the conversation but are
+/// no longer sent to the model.
+#[tauri::command]
+pub async fn insert_context_boundary(
+  app_handle: AppHandle,
+  conversation_id: String,
+) -> Result<Message, DbError> {
+  add_message(
+    &app_handle,
+    conversation_id,
+    Role::ContextBoundary.as_str().to_string(),
+    "Context cleared".to_string(),
+  )
+  .await
+}
+
+/// Drop every message up to and including the most recent context boundary
+pub fn messages_since_boundary(mut messages: Vec<Message>) -> Vec<Message> {
+  if let Some(boundary) = messages.iter().rposition(|m| m.role == Role::ContextBoundary) {
+    messages.drain(..=boundary);
+  }
+  messages
+}
+
+/// The messages to send as history for a request. When a resume summary covers the
+/// conversation, only the current message is kept from before it.
+pub fn request_history(
+  messages: Vec<Message>,
+  current_message_id: Option<&str>,
+  history_summarized: bool,
+) -> Vec<Message> {
+  let mut messages = messages_since_boundary(messages);
+  if history_summarized {
+    if let Some(current) = messages.iter().position(|m| Some(m.id.as_str()) == current_message_id) {
+      messages.drain(..current);
+    }
+  }
+  messages
+}
+
+/// Replace a user message's content and delete every message after it, so the
+/// conversation can be regenerated from the edit
+#[tauri::command]
+pub async fn edit_message(
+  app_handle: AppHandle,
+  message_id: String,
+  new_content: String,
+) -> Result<(), String> {
+  truncate_after_message(&app_handle, &message_id, Some(&new_content)).await?;
+
+  log::info!("[conversations] Edited message: {}", message_id);
+  Ok(())
+}
+
+/// Delete every message after a user message, along with their attachment files, and
+/// tell the UI which messages are gone. Only user messages can be cut after, since
+/// cutting after an assistant or function call message would leave a turn half answered.
+/// With `new_content`, the message is rewritten in the same transaction.
+pub async fn truncate_after_message(
+  app_handle: &AppHandle,
+  message_id: &str,
+  new_content: Option<&str>,
+) -> Result<Message, String> {
+  let message = get_message(app_handle.clone(), message_id.to_string()).await?;
+  if message.role != Role::User {
+    return Err(format!(
+      "Only user messages can be edited or regenerated, not {} messages",
+      message.role.as_str()
+    ));
+  }
+
+  let (deleted_ids, attachment_paths) = {
+    let state = app_handle.state::<DbState>();
+    let mut conn_guard = state
+      .0
+      .lock()
+      .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    let conn = conn_guard
+      .as_mut()
+      .ok_or("Database connection not available.".to_string())?;
+    delete_messages_after(conn, message_id, new_content)?
+  };
+
+  let app_data_dir = app_handle
+    .path()
+    .app_data_dir()
+    .map_err(|e| format!("Could not resolve app data directory: {}", e))?;
+  for path in attachment_paths {
+    let full_path = app_data_dir.join(path);
+    if let Err(e) = std::fs::remove_file(&full_path) {
+      log::warn!("[conversations] Failed to delete attachment {:?}: {}", full_path, e);
+    }
+    // The folder is per message, so remove it once it is empty
+    if let Some(parent) = full_path.parent() {
+      let _ = std::fs::remove_dir(parent);
+    }
+  }
+
+  if !deleted_ids.is_empty() {
+    log::info!(
+      "[conversations] Deleted {} messages after {}",
+      deleted_ids.len(),
+      message_id
+    );
+    let _ = emit(
+      MESSAGES_DELETED,
+      MessagesDeletedEvent {
+        conversation_id: message.conversation_id.clone(),
+        message_ids: deleted_ids,
+        timestamp: Utc::now().to_rfc3339(),
+      },
+    );
+  }
+
+  Ok(message)
+}
+
+/// Delete the messages that follow `message_id` in its conversation, replacing its content
+/// with `new_content` when given. Returns the deleted ids and the paths of their
+/// attachment files, which the caller removes from disk.
+fn delete_messages_after(
+  conn: &mut Connection,
+  message_id: &str,
+  new_content: Option<&str>,
+) -> Result<(Vec<String>, Vec<String>), String> {
+  // Messages are ordered by timestamp, with rowid breaking ties
+  const LATER_MESSAGES: &str = "SELECT later.id
+    FROM conversation_messages AS edited
+    JOIN conversation_messages AS later ON later.conversation_id = edited.conversation_id
+    WHERE edited.id = ?1 AND (later.timestamp, later.rowid) > (edited.timestamp, edited.rowid)";
+
+  let tx = conn
+    .transaction()
+    .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+  let deleted_ids = tx
+    .prepare(LATER_MESSAGES)
+    .and_then(|mut stmt| {
+      stmt
+        .query_map(params![message_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()
+    })
+    .map_err(|e| format!("Failed to query later messages: {}", e))?;
+
+  let attachment_paths = tx
+    .prepare(&format!(
+      "SELECT file_path FROM attachments WHERE file_path IS NOT NULL AND message_id IN ({})",
+      LATER_MESSAGES
+    ))
+    .and_then(|mut stmt| {
+      stmt
+        .query_map(params![message_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()
+    })
+    .map_err(|e| format!("Failed to query attachment paths: {}", e))?;
+
+  tx.execute(
+    "UPDATE conversations SET message_count = message_count - ?1, updated_at = ?2
+       WHERE id = (SELECT conversation_id FROM conversation_messages WHERE id = ?3)",
+    params![deleted_ids.len() as i64, Utc::now().to_rfc3339(), message_id],
+  )
+  .map_err(|e| format!("Failed to update conversation: {}", e))?;
+  invalidate_summary(&tx, message_id)?;
+
+  // Attachments go with their messages through ON DELETE CASCADE
+  tx.execute(
+    &format!("DELETE FROM conversation_messages WHERE id IN ({})", LATER_MESSAGES),
+    params![message_id],
+  )
+  .map_err(|e| format!("Failed to delete later messages: {}", e))?;
+
+  if let Some(content) = new_content {
+    update_message_content(&tx, message_id, content)?;
+  }
+
+  tx.commit()
+    .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+  Ok((deleted_ids, attachment_paths))
+}
+
+/// Start a new conversation from a copy of an existing one, up to and including
+/// `from_message_id`. Messages and attachments get new ids and attachment files are
+/// copied, so the original conversation is left untouched.
+#[tauri::command]
+pub async fn fork_conversation(
+  app_handle: AppHandle,
+  conversation_id: String,
+  from_message_id: String,
+) -> Result<Conversation, DbError> {
+  let (conversation, copied_files) = {
+    let state = app_handle.state::<DbState>();
+    let mut conn_guard = state
+      .0
+      .lock()
+      .map_err(|_| DbError::poisoned())?;
+    let conn = conn_guard
+      .as_mut()
+      .ok_or_else(DbError::unavailable)?;
+    copy_conversation_until(conn, &conversation_id, &from_message_id)?
+  };
+
+  let app_data_dir = app_handle
+    .path()
+    .app_data_dir()
+    .map_err(|e| DbError::Other(format!("Could not resolve app data directory: {}", e)))?;
+  for (from, to) in copied_files {
+    let target = app_data_dir.join(&to);
+    if let Some(parent) = target.parent() {
+      let _ = std::fs::create_dir_all(parent);
+    }
+    if let Err(e) = std::fs::copy(app_data_dir.join(&from), &target) {
+      log::warn!("[conversations] Failed to copy attachment {} to {}: {}", from, to, e);
+    }
+  }
+
+  log::info!(
+    "[conversations] Forked conversation {} at message {} into {}",
+    conversation_id,
+    from_message_id,
+    conversation.id
+  );
+  Ok(conversation)
+}
+
+/// Copy a conversation's messages up to `from_message_id` into a new conversation in one
+/// transaction. Returns the new conversation and the attachment files to copy on disk,
+/// as (original, copy) paths relative to the app data directory.
+fn copy_conversation_until(
+  conn: &mut Connection,
+  conversation_id: &str,
+  from_message_id: &str,
+) -> Result<(Conversation, Vec<(String, String)>), DbError> {
+  let tx = conn
+    .transaction()
+    .map_err(|e| DbError::sqlite("Failed to start transaction", e))?;
+
+  let (name, conv_type): (String, String) = tx
+    .query_row(
+      "SELECT name, conv_type FROM conversations WHERE id = ?1",
+      params![conversation_id],
+      |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .map_err(|e| DbError::sqlite("Failed to get conversation", e))?;
+
+  // Messages are ordered by timestamp, with rowid breaking ties
+  let messages: Vec<(String, String, String, String)> = tx
+    .prepare(
+      "SELECT earlier.id, earlier.role, earlier.content, earlier.timestamp
+         FROM conversation_messages AS fork_point
+         JOIN conversation_messages AS earlier ON earlier.conversation_id = fork_point.conversation_id
+         WHERE fork_point.id = ?1 AND fork_point.conversation_id = ?2
+           AND (earlier.timestamp, earlier.rowid) <= (fork_point.timestamp, fork_point.rowid)
+         ORDER BY earlier.timestamp ASC, earlier.rowid ASC",
+    )
+    .and_then(|mut stmt| {
+      stmt
+        .query_map(params![from_message_id, conversation_id], |row| {
+          Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+    })
+    .map_err(|e| DbError::sqlite("Failed to query messages", e))?;
+  if messages.is_empty() {
+    return Err(DbError::NotFound(format!(
+      "Message {} not found in conversation {}",
+      from_message_id, conversation_id
+    )));
+  }
+
+  let now = Utc::now().to_rfc3339();
+  let conversation = Conversation {
+    id: Uuid::new_v4().to_string(),
+    name: format!("{} (fork)", name),
+    conv_type,
+    created_at: now.clone(),
+    updated_at: now,
+    message_count: messages.len() as i32,
+    is_pinned: false,
+    is_archived: false,
+  };
+  tx.execute(
+    "INSERT INTO conversations (id, name, conv_type, created_at, updated_at, message_count)
+       VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    params![
+      conversation.id,
+      conversation.name,
+      conversation.conv_type,
+      conversation.created_at,
+      conversation.updated_at,
+      conversation.message_count
+    ],
+  )
+  .map_err(|e| DbError::sqlite("Failed to create conversation", e))?;
+
+  let mut copied_files = Vec::new();
+  for (id, role, content, timestamp) in messages {
+    let message_id = Uuid::new_v4().to_string();
+    tx.execute(
+      "INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+      params![message_id, conversation.id, role, content, timestamp],
+    )
+    .map_err(|e| DbError::sqlite("Failed to copy message", e))?;
+
+    let attachments = tx
+      .prepare(
+        "SELECT id, message_id, file_type, file_name, file_path, extracted_text, created_at
+           FROM attachments WHERE message_id = ?1 ORDER BY created_at ASC",
+      )
+      .and_then(|mut stmt| {
+        stmt
+          .query_map(params![id], |row| {
+            Ok(Attachment {
+              id: row.get(0)?,
+              message_id: row.get(1)?,
+              file_type: row.get(2)?,
+              file_name: row.get(3)?,
+              file_path: row.get(4)?,
+              extracted_text: row.get(5)?,
+              created_at: row.get(6)?,
+            })
+          })?
+          .collect::<Result<Vec<_>, _>>()
+      })
+      .map_err(|e| DbError::sqlite("Failed to query attachments", e))?;
+
+    for attachment in attachments {
+      // Attachment files live in a folder per message
+      let new_path = attachment.file_path.as_ref().map(|path| {
+        let file = Path::new(path).file_name().map(|f| f.to_string_lossy().to_string());
+        format!(
+          "attachments/{}/{}",
+          message_id,
+          file.unwrap_or_else(|| attachment.file_name.clone())
+        )
+      });
+      if let (Some(from), Some(to)) = (&attachment.file_path, &new_path) {
+        copied_files.push((from.clone(), to.clone()));
+      }
+      tx.execute(
+        "INSERT INTO attachments (id, message_id, file_type, file_name, file_path, extracted_text, created_at)
+           VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+          Uuid::new_v4().to_string(),
+          message_id,
+          attachment.file_type,
+          attachment.file_name,
+          new_path,
+          attachment.extracted_text,
+          attachment.created_at
+        ],
+      )
+      .map_err(|e| DbError::sqlite("Failed to copy attachment", e))?;
+    }
+  }
+
+  tx.commit()
+    .map_err(|e| DbError::sqlite("Failed to commit transaction", e))?;
+
+  Ok((conversation, copied_files))
+}
+
 /// Get all messages for a conversation
 #[tauri::command]
 pub async fn get_messages(
@@ -318,15 +738,15 @@ pub async fn get_messages(
 
 /// Get a message by its id
 #[tauri::command]
-pub async fn get_message(app_handle: AppHandle, message_id: String) -> Result<Message, String> {
+pub async fn get_message(app_handle: AppHandle, message_id: String) -> Result<Message, DbError> {
   let state = app_handle.state::<DbState>();
   let conn_guard = state
     .0
     .lock()
-    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    .map_err(|_| DbError::poisoned())?;
   let conn = conn_guard
     .as_ref()
-    .ok_or("Database connection not available.".to_string())?;
+    .ok_or_else(DbError::unavailable)?;
 
   let mut stmt = conn
     .prepare(
@@ -339,27 +759,27 @@ pub async fn get_message(app_handle: AppHandle, message_id: String) -> Result<Me
         LEFT JOIN memory_entries me ON m.id = me.message_id
         WHERE m.id = ?1",
     )
-    .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to prepare statement", e))?;
 
   let mut rows = stmt
     .query(params![message_id])
-    .map_err(|e| format!("Failed to query message: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to query message", e))?;
 
   let mut message_acc: Option<Message> = None;
 
-  while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+  while let Some(row) = rows.next()? {
     if message_acc.is_none() {
-      let role_str: String = row.get(2).map_err(|e| e.to_string())?;
-      let msg_id: String = row.get(0).map_err(|e| e.to_string())?;
+      let role_str: String = row.get(2)?;
+      let msg_id: String = row.get(0)?;
 
-      let memory = if let Some(mem_id) = row.get::<_, Option<String>>(12).map_err(|e| e.to_string())? {
+      let memory = if let Some(mem_id) = row.get::<_, Option<String>>(12)? {
         Some(MemoryEntry {
           id: mem_id,
           message_id: msg_id.clone(),
-          memory_type: row.get(13).map_err(|e| e.to_string())?,
-          text: row.get(14).map_err(|e| e.to_string())?,
+          memory_type: row.get(13)?,
+          text: row.get(14)?,
           embedding: vec![],
-          timestamp: row.get(15).map_err(|e| e.to_string())?,
+          timestamp: row.get(15)?,
           similarity: None,
         })
       } else {
@@ -368,31 +788,31 @@ pub async fn get_message(app_handle: AppHandle, message_id: String) -> Result<Me
 
       message_acc = Some(Message {
         id: msg_id,
-        conversation_id: row.get(1).map_err(|e| e.to_string())?,
+        conversation_id: row.get(1)?,
         role: Role::from_str(&role_str),
-        content: row.get(3).map_err(|e| e.to_string())?,
-        timestamp: row.get(4).map_err(|e| e.to_string())?,
+        content: row.get(3)?,
+        timestamp: row.get(4)?,
         attachments: Vec::new(),
         memory,
       });
     }
 
     if let Some(ref mut msg) = message_acc {
-      if let Some(attachment_id) = row.get::<_, Option<String>>(5).map_err(|e| e.to_string())? {
+      if let Some(attachment_id) = row.get::<_, Option<String>>(5)? {
         msg.attachments.push(Attachment {
           id: attachment_id,
-          message_id: row.get(6).map_err(|e| e.to_string())?,
-          file_type: row.get(7).map_err(|e| e.to_string())?,
-          file_name: row.get(8).map_err(|e| e.to_string())?,
-          file_path: row.get(9).map_err(|e| e.to_string())?,
-          extracted_text: row.get(10).map_err(|e| e.to_string())?,
-          created_at: row.get(11).map_err(|e| e.to_string())?,
+          message_id: row.get(6)?,
+          file_type: row.get(7)?,
+          file_name: row.get(8)?,
+          file_path: row.get(9)?,
+          extracted_text: row.get(10)?,
+          created_at: row.get(11)?,
         });
       }
     }
   }
 
-  let message = message_acc.ok_or_else(|| format!("Message not found: {}", message_id))?;
+  let message = message_acc.ok_or_else(|| DbError::NotFound(format!("Message not found: {}", message_id)))?;
 
   Ok(message)
 }
@@ -402,19 +822,20 @@ pub async fn get_message(app_handle: AppHandle, message_id: String) -> Result<Me
 pub async fn get_conversation(
   app_handle: AppHandle,
   conversation_id: String,
-) -> Result<Conversation, String> {
+) -> Result<Conversation, DbError> {
   let state = app_handle.state::<DbState>();
   let conn_guard = state
     .0
     .lock()
-    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    .map_err(|_| DbError::poisoned())?;
   let conn = conn_guard
     .as_ref()
-    .ok_or("Database connection not available.".to_string())?;
+    .ok_or_else(DbError::unavailable)?;
 
   let conversation = conn
     .query_row(
-      "SELECT id, name, conv_type, created_at, updated_at, message_count FROM conversations WHERE id = ?1",
+      "SELECT id, name, conv_type, created_at, updated_at, message_count, is_pinned, is_archived
+         FROM conversations WHERE id = ?1",
       params![conversation_id],
       |row| {
         let created_at: String = row.get(3)?;
@@ -427,20 +848,24 @@ pub async fn get_conversation(
           created_at,
           updated_at,
           message_count: row.get(5)?,
+          is_pinned: row.get(6)?,
+          is_archived: row.get(7)?,
         })
       },
     )
-    .map_err(|e| format!("Failed to get conversation: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to get conversation", e))?;
 
   Ok(conversation)
 }
 
-/// List all conversations
+/// List conversations, pinned first. Archived conversations are left out
+/// unless `include_archived` is set.
 #[tauri::command]
 pub async fn list_conversations(
   app_handle: AppHandle,
   limit: usize,
   offset: usize,
+  include_archived: Option<bool>,
 ) -> Result<Vec<Conversation>, String> {
   log::info!(
     "[conversations] Listing conversations with limit {} and offset {}",
@@ -456,17 +881,27 @@ pub async fn list_conversations(
     .as_ref()
     .ok_or("Database connection not available.".to_string())?;
 
+  query_conversations(conn, limit, offset, include_archived.unwrap_or(false))
+}
+
+fn query_conversations(
+  conn: &Connection,
+  limit: usize,
+  offset: usize,
+  include_archived: bool,
+) -> Result<Vec<Conversation>, String> {
   let mut stmt = conn
     .prepare(
-      "SELECT id, name, conv_type, created_at, updated_at, message_count 
-         FROM conversations 
-         ORDER BY updated_at DESC
+      "SELECT id, name, conv_type, created_at, updated_at, message_count, is_pinned, is_archived
+         FROM conversations
+         WHERE ?3 OR is_archived = 0
+         ORDER BY is_pinned DESC, updated_at DESC
          LIMIT ?1 OFFSET ?2",
     )
     .map_err(|e| format!("Failed to prepare statement: {}", e))?;
 
   let conversations = stmt
-    .query_map(params![limit, offset], |row| {
+    .query_map(params![limit, offset, include_archived], |row| {
       let created_at: String = row.get(3)?;
       let updated_at: String = row.get(4)?;
 
@@ -477,6 +912,8 @@ pub async fn list_conversations(
         created_at,
         updated_at,
         message_count: row.get(5)?,
+        is_pinned: row.get(6)?,
+        is_archived: row.get(7)?,
       })
     })
     .map_err(|e| format!("Failed to query conversations: {}", e))?
@@ -486,20 +923,163 @@ pub async fn list_conversations(
   Ok(conversations)
 }
 
+/// List conversations with pagination metadata
+#[tauri::command]
+pub async fn list_conversations_paged(
+  app_handle: AppHandle,
+  limit: usize,
+  offset: usize,
+  include_archived: Option<bool>,
+) -> Result<Paginated<Conversation>, String> {
+  let include_archived = include_archived.unwrap_or(false);
+  let items = list_conversations(app_handle.clone(), limit, offset, Some(include_archived)).await?;
+
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  let total = query_conversation_count(conn, include_archived)?;
+
+  Ok(Paginated::new(
+    items,
+    total,
+    offset as u64,
+    limit as u64,
+  ))
+}
+
+/// Count the conversations `list_conversations` would page through with the same filter
+#[tauri::command]
+pub async fn count_conversations(
+  app_handle: AppHandle,
+  include_archived: Option<bool>,
+) -> Result<u64, String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  query_conversation_count(conn, include_archived.unwrap_or(false))
+}
+
+fn query_conversation_count(conn: &Connection, include_archived: bool) -> Result<u64, String> {
+  let total: i64 = conn
+    .query_row(
+      "SELECT COUNT(*) FROM conversations WHERE ?1 OR is_archived = 0",
+      params![include_archived],
+      |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to count conversations: {}", e))?;
+  Ok(total as u64)
+}
+
+/// Search message content across all conversations, best matches first.
+/// `message_types` restricts results to the given roles, e.g. to leave out function calls.
+#[tauri::command]
+pub async fn search_messages(
+  app_handle: AppHandle,
+  query: String,
+  limit: usize,
+  message_types: Option<Vec<Role>>,
+) -> Result<Vec<MessageSearchResult>, String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  query_message_search(conn, &query, limit, message_types.as_deref())
+}
+
+fn query_message_search(
+  conn: &Connection,
+  query: &str,
+  limit: usize,
+  message_types: Option<&[Role]>,
+) -> Result<Vec<MessageSearchResult>, String> {
+  let Some(match_expr) = fts_match_expression(query) else {
+    return Ok(Vec::new());
+  };
+  let roles = message_types.map(|roles| {
+    serde_json::Value::from(roles.iter().map(|r| r.as_str()).collect::<Vec<_>>()).to_string()
+  });
+
+  let mut stmt = conn
+    .prepare(
+      "SELECT m.id, m.conversation_id, m.role, m.content, m.timestamp,
+         snippet(conversation_messages_fts, 0, '**', '**', '...', 12)
+         FROM conversation_messages_fts
+         JOIN conversation_messages_fts_ids ids ON ids.fts_rowid = conversation_messages_fts.rowid
+         JOIN conversation_messages m ON m.id = ids.message_id
+         WHERE conversation_messages_fts MATCH ?1
+           AND (?2 IS NULL OR m.role IN (SELECT value FROM json_each(?2)))
+         ORDER BY rank
+         LIMIT ?3",
+    )
+    .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+  let results = stmt
+    .query_map(params![match_expr, roles, limit], |row| {
+      let role_str: String = row.get(2)?;
+      Ok(MessageSearchResult {
+        message: Message {
+          id: row.get(0)?,
+          conversation_id: row.get(1)?,
+          role: Role::from_str(&role_str),
+          content: row.get(3)?,
+          timestamp: row.get(4)?,
+          attachments: Vec::new(),
+          memory: None,
+        },
+        snippet: row.get(5)?,
+      })
+    })
+    .map_err(|e| format!("Failed to search messages: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Failed to collect search results: {}", e))?;
+
+  Ok(results)
+}
+
+/// Quote each search term so user input can't be parsed as FTS5 query syntax
+fn fts_match_expression(query: &str) -> Option<String> {
+  let terms: Vec<String> = query
+    .split_whitespace()
+    .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+    .collect();
+  if terms.is_empty() {
+    None
+  } else {
+    Some(terms.join(" "))
+  }
+}
+
 /// Delete a conversation completely
 #[tauri::command]
 pub async fn delete_conversation(
   app_handle: AppHandle,
   conversation_id: String,
-) -> Result<(), String> {
+) -> Result<(), DbError> {
   let state = app_handle.state::<DbState>();
   let conn_guard = state
     .0
     .lock()
-    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    .map_err(|_| DbError::poisoned())?;
   let conn = conn_guard
     .as_ref()
-    .ok_or("Database connection not available.".to_string())?;
+    .ok_or_else(DbError::unavailable)?;
 
   // Ensure conversation exists
   let _conversation_exists: String = conn
@@ -508,7 +1088,7 @@ pub async fn delete_conversation(
       params![conversation_id],
       |row| row.get(0),
     )
-    .map_err(|_| format!("Conversation not found: {}", conversation_id))?;
+    .map_err(|e| DbError::sqlite("Failed to load conversation", e))?;
 
   // Clean up attachments associated with messages in the conversation
   let mut stmt = conn
@@ -518,11 +1098,11 @@ pub async fn delete_conversation(
          JOIN conversation_messages m ON a.message_id = m.id
          WHERE m.conversation_id = ?1",
     )
-    .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to prepare statement", e))?;
 
   let attachment_paths = stmt
     .query_map(params![conversation_id], |row| row.get::<_, Option<String>>(0))
-    .map_err(|e| format!("Failed to query attachment paths: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to query attachment paths", e))?;
   // Create set of parent dirs to delete later
   let mut parent_dirs = std::collections::HashSet::new();
   for path_result in attachment_paths {
@@ -530,11 +1110,11 @@ pub async fn delete_conversation(
       let full_path = app_handle
         .path()
         .app_data_dir()
-        .map_err(|e| format!("Could not resolve app data directory: {}", e))?
+        .map_err(|e| DbError::Other(format!("Could not resolve app data directory: {}", e)))?
         .join(file_path);
       if full_path.exists() {
         std::fs::remove_file(&full_path)
-          .map_err(|e| format!("Failed to delete attachment file: {}", e))?;
+          .map_err(|e| DbError::Other(format!("Failed to delete attachment file: {}", e)))?;
         if let Some(parent) = full_path.parent() {
           parent_dirs.insert(parent.to_path_buf());
         }
@@ -544,17 +1124,20 @@ pub async fn delete_conversation(
   for dir in parent_dirs {
     if dir.exists() {
       std::fs::remove_dir_all(&dir)
-        .map_err(|e| format!("Failed to delete attachment directory: {}", e))?;
+        .map_err(|e| DbError::Other(format!("Failed to delete attachment directory: {}", e)))?;
     }
   }
 
+  // Debug log rows hold the conversation's prompts and history
+  crate::db::llm_debug::delete_exchanges(conn, Some(&conversation_id)).map_err(DbError::Other)?;
+
   // Delete conversation
   conn
     .execute(
       "DELETE FROM conversations WHERE id = ?1",
       params![conversation_id],
     )
-    .map_err(|e| format!("Failed to delete conversation: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to delete conversation", e))?;
 
   log::info!("[conversations] Deleted conversation: {}", conversation_id);
   Ok(())
@@ -566,23 +1149,26 @@ pub async fn update_conversation_name(
   app_handle: AppHandle,
   conversation_id: String,
   name: String,
-) -> Result<(), String> {
+) -> Result<(), DbError> {
   let state = app_handle.state::<DbState>();
   let conn_guard = state
     .0
     .lock()
-    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+    .map_err(|_| DbError::poisoned())?;
   let conn = conn_guard
     .as_ref()
-    .ok_or("Database connection not available.".to_string())?;
+    .ok_or_else(DbError::unavailable)?;
 
   let now = Utc::now();
-  conn
+  let updated = conn
     .execute(
       "UPDATE conversations SET name = ?1, updated_at = ?2 WHERE id = ?3",
       params![name, now.to_rfc3339(), conversation_id],
     )
-    .map_err(|e| format!("Failed to update conversation name: {}", e))?;
+    .map_err(|e| DbError::sqlite("Failed to update conversation name", e))?;
+  if updated == 0 {
+    return Err(DbError::NotFound(format!("Conversation not found: {}", conversation_id)));
+  }
 
   log::info!(
     "[conversations] Updated conversation name: {}",
@@ -591,6 +1177,257 @@ pub async fn update_conversation_name(
   Ok(())
 }
 
+/// Pin or unpin a conversation so it lists before the rest
+#[tauri::command]
+pub async fn set_conversation_pinned(
+  app_handle: AppHandle,
+  conversation_id: String,
+  pinned: bool,
+) -> Result<(), String> {
+  set_conversation_flag(&app_handle, &conversation_id, "is_pinned", pinned)
+}
+
+/// Archive or restore a conversation. Archived conversations are hidden from the list.
+#[tauri::command]
+pub async fn set_conversation_archived(
+  app_handle: AppHandle,
+  conversation_id: String,
+  archived: bool,
+) -> Result<(), String> {
+  set_conversation_flag(&app_handle, &conversation_id, "is_archived", archived)
+}
+
+fn set_conversation_flag(
+  app_handle: &AppHandle,
+  conversation_id: &str,
+  column: &'static str,
+  value: bool,
+) -> Result<(), String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  let rows = conn
+    .execute(
+      &format!("UPDATE conversations SET {} = ?1 WHERE id = ?2", column),
+      params![value, conversation_id],
+    )
+    .map_err(|e| format!("Failed to update {}: {}", column, e))?;
+  if rows == 0 {
+    return Err(format!("Conversation not found: {}", conversation_id));
+  }
+
+  log::info!(
+    "[conversations] Set {} = {} for conversation: {}",
+    column,
+    value,
+    conversation_id
+  );
+  Ok(())
+}
+
+/// Export a conversation transcript as `"markdown"` or `"json"`
+#[tauri::command]
+pub async fn export_conversation(
+  app_handle: AppHandle,
+  conversation_id: String,
+  format: String,
+) -> Result<String, String> {
+  let conversation = get_conversation(app_handle.clone(), conversation_id.clone()).await?;
+  let messages = get_messages(app_handle, conversation_id).await?;
+
+  match format.as_str() {
+    "markdown" => Ok(render_markdown(&conversation, &messages)),
+    "json" => serde_json::to_string_pretty(&messages)
+      .map_err(|e| format!("Failed to serialize conversation: {}", e)),
+    other => Err(format!("Unsupported export format: {}", other)),
+  }
+}
+
+/// Render messages under role headers, with function calls as code blocks
+fn render_markdown(conversation: &Conversation, messages: &[Message]) -> String {
+  let mut out = format!("# {}\n", conversation.name);
+
+  for message in messages {
+    let header = match message.role {
+      Role::System => "System",
+      Role::User => "User",
+      Role::Assistant => "Assistant",
+      Role::FunctionCall => "Function call",
+      Role::ContextBoundary => "Context cleared",
+    };
+    out.push_str(&format!("\n## {}\n\n", header));
+
+    match message.role {
+      Role::FunctionCall => out.push_str(&format!("```\n{}\n```\n", message.content.trim_end())),
+      // The header says it all
+      Role::ContextBoundary => {}
+      _ => out.push_str(&format!("{}\n", message.content.trim_end())),
+    }
+
+    if !message.attachments.is_empty() {
+      let names: Vec<&str> = message.attachments.iter().map(|a| a.file_name.as_str()).collect();
+      out.push_str(&format!("\n_Attachments: {}_\n", names.join(", ")));
+    }
+    if let Some(memory) = &message.memory {
+      out.push_str(&format!("\n_Remembered: {}_\n", memory.text));
+    }
+  }
+
+  out
+}
+
+/// Get the cached resume summary for a conversation, if it is still current
+pub fn get_conversation_summary(
+  app_handle: &AppHandle,
+  conversation_id: &str,
+) -> Result<Option<String>, String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  query_current_summary(conn, conversation_id)
+}
+
+/// Cache a resume summary against the conversation's current message count
+pub fn save_conversation_summary(
+  app_handle: &AppHandle,
+  conversation_id: &str,
+  summary: &str,
+) -> Result<(), String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  store_summary(conn, conversation_id, summary)
+}
+
+fn query_current_summary(conn: &Connection, conversation_id: &str) -> Result<Option<String>, String> {
+  // A summary goes stale as soon as a message is added
+  conn
+    .query_row(
+      "SELECT summary FROM conversations
+         WHERE id = ?1 AND summary IS NOT NULL AND summary_message_count = message_count",
+      params![conversation_id],
+      |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to get conversation summary: {}", e))
+}
+
+/// Drop the cached summary of the conversation `message_id` belongs to. Edits and
+/// deletions change history the summary covers, and the message count alone can end up
+/// matching again.
+fn invalidate_summary(conn: &Connection, message_id: &str) -> Result<(), String> {
+  conn
+    .execute(
+      "UPDATE conversations SET summary = NULL, summary_message_count = NULL
+         WHERE id = (SELECT conversation_id FROM conversation_messages WHERE id = ?1)",
+      params![message_id],
+    )
+    .map_err(|e| format!("Failed to clear conversation summary: {}", e))?;
+  Ok(())
+}
+
+fn store_summary(conn: &Connection, conversation_id: &str, summary: &str) -> Result<(), String> {
+  conn
+    .execute(
+      "UPDATE conversations SET summary = ?1, summary_message_count = message_count WHERE id = ?2",
+      params![summary, conversation_id],
+    )
+    .map_err(|e| format!("Failed to save conversation summary: {}", e))?;
+  Ok(())
+}
+
+/// Whether responses in a conversation should be streamed (defaults to true)
+#[tauri::command]
+pub fn get_conversation_streaming(
+  app_handle: AppHandle,
+  conversation_id: String,
+) -> Result<bool, String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  query_stream_preference(conn, &conversation_id)
+}
+
+/// Set whether responses in a conversation should be streamed
+#[tauri::command]
+pub fn set_conversation_streaming(
+  app_handle: AppHandle,
+  conversation_id: String,
+  enabled: bool,
+) -> Result<(), String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  store_stream_preference(conn, &conversation_id, enabled)?;
+
+  log::info!(
+    "[conversations] Set streaming to {} for conversation: {}",
+    enabled,
+    conversation_id
+  );
+  Ok(())
+}
+
+fn store_stream_preference(conn: &Connection, conversation_id: &str, enabled: bool) -> Result<(), String> {
+  let updated = conn
+    .execute(
+      "UPDATE conversations SET stream_responses = ?1 WHERE id = ?2",
+      params![enabled, conversation_id],
+    )
+    .map_err(|e| format!("Failed to update streaming preference: {}", e))?;
+  if updated == 0 {
+    return Err(format!("Conversation not found: {}", conversation_id));
+  }
+  Ok(())
+}
+
+fn query_stream_preference(conn: &Connection, conversation_id: &str) -> Result<bool, String> {
+  let preference: Option<Option<bool>> = conn
+    .query_row(
+      "SELECT stream_responses FROM conversations WHERE id = ?1",
+      params![conversation_id],
+      |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to get streaming preference: {}", e))?;
+  Ok(preference.flatten().unwrap_or(true))
+}
+
+/// Attachment types whose content is stored as text rather than a file on disk
+pub fn is_text_attachment(file_type: &str) -> bool {
+  matches!(file_type, "ambient/ocr" | "ambient/audio-transcript")
+}
+
 /// Create attachments and save to disk
 pub async fn create_attachments(
   app_handle: &AppHandle,
@@ -602,17 +1439,18 @@ pub async fn create_attachments(
 
   for data in attachment_data {
     let attachment_id = Uuid::new_v4().to_string();
-    let file_path = match data.file_type.as_str() {
-      "ambient/ocr" => None,
-      _ => Some(format!(
-      "attachments/{}/{}",
-      message_id,
-      data.name.replace("/", "_")
-      )),
+    let file_path = if is_text_attachment(&data.file_type) {
+      None
+    } else {
+      Some(format!(
+        "attachments/{}/{}",
+        message_id,
+        data.name.replace("/", "_")
+      ))
     };
 
-    // Save to disk if not ocr
-    if data.file_type != "ambient/ocr" {
+    // Save to disk if not ocr or a transcript
+    if !is_text_attachment(&data.file_type) {
       let full_path = app_handle
         .path()
         .app_data_dir()
@@ -636,9 +1474,10 @@ pub async fn create_attachments(
         .map_err(|e| format!("Failed to write attachment file: {}", e))?;
     }
 
-    let extracted_text = match data.file_type.as_str() {
-      "ambient/ocr" => Some(data.data.clone()),
-      _ => None,
+    let extracted_text = if is_text_attachment(&data.file_type) {
+      Some(data.data.clone())
+    } else {
+      None
     };
 
     let attachment = Attachment {
@@ -718,4 +1557,530 @@ pub async fn add_attachments(
     message_id
   );
   Ok(created_attachments)
-}
\ No newline at end of file
+}
+
+/// Delete attachment folders whose message has no attachment rows, left behind when the
+/// app crashed or the insert failed after the files were written. Returns the number of
+/// files removed.
+pub fn cleanup_orphaned_attachments(
+  app_handle: &AppHandle,
+  conn: &Connection,
+) -> Result<usize, String> {
+  let attachments_dir = app_handle
+    .path()
+    .app_data_dir()
+    .map_err(|e| format!("Could not resolve app data directory: {}", e))?
+    .join("attachments");
+  if !attachments_dir.is_dir() {
+    return Ok(0);
+  }
+
+  let (files, bytes) = remove_orphaned_attachment_dirs(&attachments_dir, conn)?;
+  if files > 0 {
+    log::info!(
+      "[conversations] Removed {} orphaned attachment files ({} bytes)",
+      files,
+      bytes
+    );
+  }
+  Ok(files)
+}
+
+fn remove_orphaned_attachment_dirs(
+  attachments_dir: &Path,
+  conn: &Connection,
+) -> Result<(usize, u64), String> {
+  let entries = std::fs::read_dir(attachments_dir)
+    .map_err(|e| format!("Failed to read attachments directory: {}", e))?;
+
+  let mut removed_files = 0;
+  let mut removed_bytes = 0;
+  for entry in entries.flatten() {
+    // Only real directories named by message id; symlinks could point outside the tree
+    if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+      continue;
+    }
+    let Some(message_id) = entry.file_name().to_str().map(str::to_string) else {
+      continue;
+    };
+
+    let has_rows: bool = conn
+      .query_row(
+        "SELECT EXISTS(SELECT 1 FROM attachments WHERE message_id = ?1)",
+        params![message_id],
+        |row| row.get(0),
+      )
+      .map_err(|e| format!("Failed to query attachments: {}", e))?;
+    if has_rows {
+      continue;
+    }
+
+    let path = entry.path();
+    let files = count_files(&path);
+    let bytes = crate::storage::dir_size(&path);
+    match std::fs::remove_dir_all(&path) {
+      Ok(()) => {
+        removed_files += files;
+        removed_bytes += bytes;
+      }
+      Err(e) => log::warn!(
+        "[conversations] Failed to remove orphaned attachments for {}: {}",
+        message_id,
+        e
+      ),
+    }
+  }
+
+  Ok((removed_files, removed_bytes))
+}
+
+fn count_files(dir: &Path) -> usize {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return 0;
+  };
+  entries
+    .flatten()
+    .map(|entry| match entry.file_type() {
+      Ok(t) if t.is_dir() => count_files(&entry.path()),
+      Ok(t) if t.is_file() => 1,
+      _ => 0,
+    })
+    .sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_summary_is_invalidated_by_new_messages() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute(
+        "INSERT INTO conversations (id, name, created_at, updated_at, message_count)
+           VALUES ('conv-1', 'Chat', '', '', 40)",
+        [],
+      )
+      .unwrap();
+
+    assert_eq!(query_current_summary(&conn, "conv-1").unwrap(), None);
+
+    store_summary(&conn, "conv-1", "Planning a trip to Japan").unwrap();
+    assert_eq!(
+      query_current_summary(&conn, "conv-1").unwrap().as_deref(),
+      Some("Planning a trip to Japan")
+    );
+
+    conn
+      .execute("UPDATE conversations SET message_count = message_count + 1", [])
+      .unwrap();
+    assert_eq!(query_current_summary(&conn, "conv-1").unwrap(), None);
+  }
+
+  #[test]
+  fn test_summary_is_invalidated_by_edits_and_deletes() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at, message_count)
+           VALUES ('conv-1', 'Chat', '', '', 3);
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp) VALUES
+          ('q1', 'conv-1', 'user', 'Plan a trip to Japan', '1'),
+          ('a1', 'conv-1', 'assistant', 'Sure', '2'),
+          ('q2', 'conv-1', 'user', 'In April', '3');",
+      )
+      .unwrap();
+
+    // Deleting one message and adding another would bring the count back in line
+    store_summary(&conn, "conv-1", "Planning a trip to Japan").unwrap();
+    delete_messages_after(&mut conn, "q1", None).unwrap();
+    conn
+      .execute("UPDATE conversations SET message_count = 3 WHERE id = 'conv-1'", [])
+      .unwrap();
+    assert_eq!(query_current_summary(&conn, "conv-1").unwrap(), None);
+
+    // Editing changes what the summary covers, and lands with the truncation
+    store_summary(&conn, "conv-1", "Planning a trip to Japan").unwrap();
+    delete_messages_after(&mut conn, "q1", Some("Plan a trip to Korea")).unwrap();
+    assert_eq!(query_current_summary(&conn, "conv-1").unwrap(), None);
+    let content: String = conn
+      .query_row("SELECT content FROM conversation_messages WHERE id = 'q1'", [], |r| r.get(0))
+      .unwrap();
+    assert_eq!(content, "Plan a trip to Korea");
+  }
+
+  #[test]
+  fn test_stream_preference_defaults_to_streaming() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute(
+        "INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('conv-1', 'Chat', '', '')",
+        [],
+      )
+      .unwrap();
+
+    assert!(query_stream_preference(&conn, "conv-1").unwrap());
+    store_stream_preference(&conn, "conv-1", false).unwrap();
+    assert!(!query_stream_preference(&conn, "conv-1").unwrap());
+    store_stream_preference(&conn, "conv-1", true).unwrap();
+    assert!(query_stream_preference(&conn, "conv-1").unwrap());
+
+    assert!(query_stream_preference(&conn, "missing").unwrap());
+    assert!(store_stream_preference(&conn, "missing", false).is_err());
+  }
+
+  #[test]
+  fn test_role_uses_exported_names_in_json() {
+    // search_messages receives message types from the UI as the exported TS values
+    let roles: Vec<Role> = serde_json::from_str(r#"["user", "functioncall"]"#).unwrap();
+    assert_eq!(roles, [Role::User, Role::FunctionCall]);
+    assert_eq!(serde_json::to_value(Role::ContextBoundary).unwrap(), "contextboundary");
+  }
+
+  #[test]
+  fn test_search_messages_matches_content_and_filters_roles() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('conv-1', 'Trip', '', '');
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp) VALUES
+          ('m1', 'conv-1', 'user', 'Find me flights to Tokyo in April', '1'),
+          ('m2', 'conv-1', 'assistant', 'Here are some Tokyo flights', '2'),
+          ('m3', 'conv-1', 'functioncall', 'search_flights Tokyo', '3'),
+          ('m4', 'conv-1', 'user', 'What about hotels?', '4');",
+      )
+      .unwrap();
+
+    let all = query_message_search(&conn, "tokyo", 10, None).unwrap();
+    assert_eq!(all.len(), 3);
+    assert!(all.iter().all(|r| r.message.conversation_id == "conv-1"));
+    assert!(all.iter().any(|r| r.snippet.contains("**Tokyo**")));
+
+    let chat_only =
+      query_message_search(&conn, "tokyo", 10, Some(&[Role::User, Role::Assistant])).unwrap();
+    assert_eq!(chat_only.len(), 2);
+    assert!(chat_only.iter().all(|r| r.message.role != Role::FunctionCall));
+
+    // Index follows edits and deletes
+    conn
+      .execute("UPDATE conversation_messages SET content = 'Hotels in Kyoto' WHERE id = 'm4'", [])
+      .unwrap();
+    conn.execute("DELETE FROM conversation_messages WHERE id = 'm1'", []).unwrap();
+    assert_eq!(query_message_search(&conn, "kyoto", 10, None).unwrap()[0].message.id, "m4");
+    assert_eq!(query_message_search(&conn, "april", 10, None).unwrap().len(), 0);
+
+    // VACUUM may renumber rowids; results stay tied to the right message
+    conn.execute_batch("VACUUM").unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversation_messages_fts(conversation_messages_fts) VALUES ('integrity-check')",
+      )
+      .unwrap();
+    assert_eq!(query_message_search(&conn, "kyoto", 10, None).unwrap()[0].message.id, "m4");
+    let after_vacuum = query_message_search(&conn, "flights", 10, None).unwrap();
+    assert_eq!(after_vacuum.len(), 2);
+    assert!(after_vacuum.iter().all(|r| r.message.content.to_lowercase().contains("flights")));
+
+    // Query syntax characters are treated as plain text
+    assert!(query_message_search(&conn, "\"tokyo AND -(", 10, None).is_ok());
+    assert!(query_message_search(&conn, "   ", 10, None).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_pinned_conversations_list_first_and_archived_are_hidden() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at, is_pinned, is_archived) VALUES
+          ('old-pinned', 'A', '', '2024-01-01', 1, 0),
+          ('recent', 'B', '', '2024-03-01', 0, 0),
+          ('archived', 'C', '', '2024-04-01', 0, 1),
+          ('older', 'D', '', '2024-02-01', 0, 0);",
+      )
+      .unwrap();
+
+    let ids = |include_archived| -> Vec<String> {
+      query_conversations(&conn, 10, 0, include_archived)
+        .unwrap()
+        .into_iter()
+        .map(|c| c.id)
+        .collect()
+    };
+    assert_eq!(ids(false), ["old-pinned", "recent", "older"]);
+    assert_eq!(ids(true), ["old-pinned", "archived", "recent", "older"]);
+    assert_eq!(query_conversation_count(&conn, false).unwrap(), 3);
+    assert_eq!(query_conversation_count(&conn, true).unwrap(), 4);
+  }
+
+  #[test]
+  fn test_markdown_export_renders_roles_calls_and_attachments() {
+    let message = |id: &str, role: Role, content: &str| Message {
+      id: id.to_string(),
+      conversation_id: "conv-1".to_string(),
+      role,
+      content: content.to_string(),
+      timestamp: id.to_string(),
+      attachments: Vec::new(),
+      memory: None,
+    };
+    let conversation = Conversation {
+      id: "conv-1".to_string(),
+      name: "Trip planning".to_string(),
+      conv_type: "chat".to_string(),
+      created_at: String::new(),
+      updated_at: String::new(),
+      message_count: 3,
+      is_pinned: false,
+      is_archived: false,
+    };
+
+    let mut question = message("1", Role::User, "Find flights to Tokyo");
+    question.attachments.push(Attachment {
+      id: "a1".to_string(),
+      message_id: "1".to_string(),
+      file_type: "image/png".to_string(),
+      file_name: "itinerary.png".to_string(),
+      file_path: None,
+      extracted_text: None,
+      created_at: String::new(),
+    });
+    let messages = vec![
+      question,
+      message("2", Role::FunctionCall, "search_flights {\"to\": \"HND\"}"),
+      message("3", Role::Assistant, "Two nonstop options leave Friday.\n"),
+    ];
+
+    assert_eq!(
+      render_markdown(&conversation, &messages),
+      "# Trip planning\n\
+       \n## User\n\nFind flights to Tokyo\n\
+       \n_Attachments: itinerary.png_\n\
+       \n## Function call\n\n```\nsearch_flights {\"to\": \"HND\"}\n```\n\
+       \n## Assistant\n\nTwo nonstop options leave Friday.\n"
+    );
+  }
+
+  #[test]
+  fn test_orphaned_attachment_dirs_are_removed() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('conv-1', 'Chat', '', '');
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp) VALUES
+          ('kept', 'conv-1', 'user', 'See attached', '1');
+        INSERT INTO attachments (id, message_id, file_type, file_name, file_path, created_at) VALUES
+          ('a1', 'kept', 'image/png', 'a.png', 'attachments/kept/a.png', '');",
+      )
+      .unwrap();
+
+    let root = std::env::temp_dir().join(format!("ambient-attachments-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("kept")).unwrap();
+    std::fs::create_dir_all(root.join("orphan")).unwrap();
+    std::fs::write(root.join("kept").join("a.png"), vec![0u8; 10]).unwrap();
+    std::fs::write(root.join("orphan").join("b.png"), vec![0u8; 30]).unwrap();
+    std::fs::write(root.join("orphan").join("c.pdf"), vec![0u8; 5]).unwrap();
+
+    let removed = remove_orphaned_attachment_dirs(&root, &conn).unwrap();
+    let kept = root.join("kept").join("a.png").exists();
+    let orphan = root.join("orphan").exists();
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(removed, (2, 35));
+    assert!(kept);
+    assert!(!orphan);
+  }
+
+  #[test]
+  fn test_messages_before_the_last_context_boundary_are_dropped() {
+    let message = |id: &str, role: Role| Message {
+      id: id.to_string(),
+      conversation_id: "conv-1".to_string(),
+      role,
+      content: String::new(),
+      timestamp: String::new(),
+      attachments: vec![],
+      memory: None,
+    };
+    let ids = |messages: Vec<Message>| -> Vec<String> { messages.into_iter().map(|m| m.id).collect() };
+
+    let history = vec![
+      message("q1", Role::User),
+      message("b1", Role::ContextBoundary),
+      message("q2", Role::User),
+      message("b2", Role::ContextBoundary),
+      message("q3", Role::User),
+      message("a3", Role::Assistant),
+    ];
+    assert_eq!(ids(messages_since_boundary(history)), ["q3", "a3"]);
+
+    let no_boundary = vec![message("q1", Role::User), message("a1", Role::Assistant)];
+    assert_eq!(ids(messages_since_boundary(no_boundary)), ["q1", "a1"]);
+  }
+
+  #[test]
+  fn test_summarized_history_is_left_out_of_the_request() {
+    let message = |id: &str, role: Role| Message {
+      id: id.to_string(),
+      conversation_id: "conv-1".to_string(),
+      role,
+      content: String::new(),
+      timestamp: String::new(),
+      attachments: vec![],
+      memory: None,
+    };
+    let ids = |messages: Vec<Message>| -> Vec<String> { messages.into_iter().map(|m| m.id).collect() };
+    let history = || {
+      vec![
+        message("q1", Role::User),
+        message("a1", Role::Assistant),
+        message("q2", Role::User),
+        message("a2", Role::Assistant),
+        message("q3", Role::User),
+      ]
+    };
+
+    assert_eq!(ids(request_history(history(), Some("q3"), true)), ["q3"]);
+    assert_eq!(
+      ids(request_history(history(), Some("q3"), false)),
+      ["q1", "a1", "q2", "a2", "q3"]
+    );
+    // Only messages before the current one are dropped
+    assert_eq!(ids(request_history(history(), Some("q2"), true)), ["q2", "a2", "q3"]);
+  }
+
+  #[test]
+  fn test_fork_copies_messages_up_to_the_fork_point() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at, message_count) VALUES
+          ('conv-1', 'Chat', '', '', 4);
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp) VALUES
+          ('q1', 'conv-1', 'user', 'First question', '1'),
+          ('a1', 'conv-1', 'functioncall', 'search {}', '1'),
+          ('q2', 'conv-1', 'user', 'Second question', '2'),
+          ('a2', 'conv-1', 'assistant', 'Second answer', '3');
+        INSERT INTO attachments (id, message_id, file_type, file_name, file_path, created_at) VALUES
+          ('att', 'q2', 'image/png', 'shot.png', 'attachments/q2/shot.png', ''),
+          ('ocr', 'q2', 'ambient/ocr', 'screen', NULL, '');",
+      )
+      .unwrap();
+
+    let (fork, files) = copy_conversation_until(&mut conn, "conv-1", "q2").unwrap();
+    assert_eq!(fork.name, "Chat (fork)");
+    assert_eq!(fork.message_count, 3);
+
+    let copied: Vec<(String, String, String)> = conn
+      .prepare(
+        "SELECT id, role, content FROM conversation_messages
+           WHERE conversation_id = ?1 ORDER BY timestamp, rowid",
+      )
+      .unwrap()
+      .query_map(params![fork.id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+      .unwrap()
+      .collect::<Result<_, _>>()
+      .unwrap();
+    let contents: Vec<&str> = copied.iter().map(|(_, _, content)| content.as_str()).collect();
+    assert_eq!(contents, ["First question", "search {}", "Second question"]);
+    assert_eq!(copied[1].1, "functioncall");
+    assert!(copied.iter().all(|(id, _, _)| !["q1", "a1", "q2"].contains(&id.as_str())));
+
+    let attachment_count: i64 = conn
+      .query_row(
+        "SELECT COUNT(*) FROM attachments WHERE message_id = ?1",
+        params![copied[2].0],
+        |row| row.get(0),
+      )
+      .unwrap();
+    assert_eq!(attachment_count, 2);
+    assert_eq!(
+      files,
+      [("attachments/q2/shot.png".to_string(), format!("attachments/{}/shot.png", copied[2].0))]
+    );
+
+    let original: i64 = conn
+      .query_row("SELECT COUNT(*) FROM conversation_messages WHERE conversation_id = 'conv-1'", [], |row| row.get(0))
+      .unwrap();
+    assert_eq!(original, 4);
+    assert!(matches!(
+      copy_conversation_until(&mut conn, "conv-1", "missing"),
+      Err(DbError::NotFound(_))
+    ));
+  }
+
+  #[test]
+  fn test_messages_after_an_edit_are_deleted() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at, message_count) VALUES
+          ('conv-1', 'Chat', '', '', 4), ('conv-2', 'Other', '', '', 1);
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp) VALUES
+          ('q1', 'conv-1', 'user', 'First question', '1'),
+          ('a1', 'conv-1', 'assistant', 'First answer', '2'),
+          ('q2', 'conv-1', 'user', 'Second question', '3'),
+          ('a2', 'conv-1', 'assistant', 'Second answer', '4'),
+          ('x1', 'conv-2', 'user', 'Unrelated', '5');
+        INSERT INTO attachments (id, message_id, file_type, file_name, file_path, created_at) VALUES
+          ('att', 'q2', 'image/png', 'shot.png', 'attachments/q2/shot.png', ''),
+          ('ocr', 'q2', 'ambient/ocr', 'screen', NULL, '');",
+      )
+      .unwrap();
+
+    let (ids, paths) = delete_messages_after(&mut conn, "q1", None).unwrap();
+    assert_eq!(ids, ["a1", "q2", "a2"]);
+    assert_eq!(paths, ["attachments/q2/shot.png"]);
+
+    let remaining: Vec<String> = conn
+      .prepare("SELECT id FROM conversation_messages ORDER BY timestamp")
+      .unwrap()
+      .query_map([], |row| row.get(0))
+      .unwrap()
+      .collect::<Result<_, _>>()
+      .unwrap();
+    assert_eq!(remaining, ["q1", "x1"]);
+    let attachments: i64 = conn
+      .query_row("SELECT COUNT(*) FROM attachments", [], |row| row.get(0))
+      .unwrap();
+    assert_eq!(attachments, 0);
+    let count: i32 = conn
+      .query_row("SELECT message_count FROM conversations WHERE id = 'conv-1'", [], |row| row.get(0))
+      .unwrap();
+    assert_eq!(count, 1);
+
+    // Nothing follows the last message
+    assert!(delete_messages_after(&mut conn, "q1", None).unwrap().0.is_empty());
+  }
+
+  #[test]
+  fn test_streamed_content_updates_existing_message() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('conv-1', 'Chat', '', '');
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp)
+          VALUES ('m1', 'conv-1', 'assistant', 'Hel', '1');",
+      )
+      .unwrap();
+
+    assert!(update_message_content(&conn, "m1", "Hello, wor").unwrap());
+    assert!(update_message_content(&conn, "m1", "Hello, world!").unwrap());
+    let content: String = conn
+      .query_row("SELECT content FROM conversation_messages WHERE id = 'm1'", [], |row| row.get(0))
+      .unwrap();
+    assert_eq!(content, "Hello, world!");
+    assert_eq!(query_message_search(&conn, "world", 10, None).unwrap()[0].message.id, "m1");
+
+    // Nothing saved yet: the caller inserts instead
+    assert!(!update_message_content(&conn, "m2", "partial").unwrap());
+  }
+}
@@ -1,22 +1,119 @@
+use crate::constants::DB_BUSY_TIMEOUT;
 use once_cell::sync::Lazy;
 use rusqlite::types::{Value as RusqliteValue, ValueRef};
 use rusqlite::{
-  ffi::sqlite3_auto_extension, params_from_iter, Connection, Result as RusqliteResult,
+  ffi::{sqlite3_auto_extension, sqlite3_reset_auto_extension},
+  params_from_iter, Connection, ErrorCode, Result as RusqliteResult,
 };
-use rusqlite_migration::{Migrations, M};
+use rusqlite_migration::{Migrations, SchemaVersion, M};
+use serde::Serialize;
 use serde_json::Value as JsonValue;
 use sqlite_vec::sqlite3_vec_init;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use tauri::Manager;
+use ts_rs::TS;
 
 pub struct DbState(pub Mutex<Option<Connection>>);
 
-// Database schema migrations
-static MIGRATIONS: Lazy<Migrations<'static>> = Lazy::new(|| {
-  Migrations::new(vec![M::up(
-    r#"
+/// Error returned by database commands. Serialized as `{ kind, message }` so the UI
+/// can tell a missing record from a busy database.
+#[derive(Debug, Serialize, TS)]
+#[serde(tag = "kind", content = "message")]
+#[ts(export, export_to = "db.ts")]
+pub enum DbError {
+  NotFound(String),
+  /// SQLite reported the database busy or locked; retrying may succeed
+  Locked,
+  Constraint(String),
+  Serialization(String),
+  /// The database was written by a newer build; holds its schema version
+  SchemaTooNew(u32),
+  Other(String),
+}
+
+impl DbError {
+  /// Classify a SQLite error, prefixing its message with `context`
+  pub fn sqlite(context: &str, error: rusqlite::Error) -> Self {
+    let message = format!("{}: {}", context, error);
+    Self::classify(&error, message)
+  }
+
+  pub fn unavailable() -> Self {
+    DbError::Other("Database connection not available.".to_string())
+  }
+
+  /// The connection mutex only fails when a thread panicked while holding it, which
+  /// retrying won't fix
+  pub fn poisoned() -> Self {
+    DbError::Other("Database connection is unusable after a previous failure.".to_string())
+  }
+
+  fn classify(error: &rusqlite::Error, message: String) -> Self {
+    match error {
+      rusqlite::Error::QueryReturnedNoRows => DbError::NotFound(message),
+      rusqlite::Error::SqliteFailure(e, _) => match e.code {
+        ErrorCode::ConstraintViolation => DbError::Constraint(message),
+        ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => DbError::Locked,
+        _ => DbError::Other(message),
+      },
+      rusqlite::Error::FromSqlConversionFailure(..)
+      | rusqlite::Error::InvalidColumnType(..)
+      | rusqlite::Error::IntegralValueOutOfRange(..) => DbError::Serialization(message),
+      _ => DbError::Other(message),
+    }
+  }
+}
+
+impl From<rusqlite::Error> for DbError {
+  fn from(error: rusqlite::Error) -> Self {
+    let message = error.to_string();
+    Self::classify(&error, message)
+  }
+}
+
+impl std::fmt::Display for DbError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DbError::NotFound(msg)
+      | DbError::Constraint(msg)
+      | DbError::Serialization(msg)
+      | DbError::Other(msg) => write!(f, "{}", msg),
+      DbError::Locked => write!(f, "Database is busy, please try again"),
+      DbError::SchemaTooNew(version) => write!(
+        f,
+        "Database was created by a newer version of Ambient and can't be opened by this one \
+         (schema version {})",
+        version
+      ),
+    }
+  }
+}
+
+impl std::error::Error for DbError {}
+
+/// Keeps `?` working in callers that still return String errors
+impl From<DbError> for String {
+  fn from(error: DbError) -> Self {
+    error.to_string()
+  }
+}
+
+/// Set once the database is initialized; false when sqlite_vec failed to load
+static VEC_AVAILABLE: AtomicBool = AtomicBool::new(false);
+
+pub const VEC_UNAVAILABLE_ERROR: &str =
+  "Vector search unavailable: the sqlite_vec extension failed to load";
+
+/// Vector index for memory similarity search, requires sqlite_vec
+const VEC_SCHEMA: &str =
+  "CREATE VIRTUAL TABLE IF NOT EXISTS memory_entries_vec USING vec0(embedding float[768]);";
+
+/// Schema of the first release. Kept as shipped; `migrations_for` drops the vector
+/// table from it when sqlite_vec is unavailable.
+const INITIAL_SCHEMA: &str = r#"
         -- Conversation tables
         CREATE TABLE IF NOT EXISTS conversations (
           id TEXT PRIMARY KEY,
@@ -108,11 +205,128 @@ static MIGRATIONS: Lazy<Migrations<'static>> = Lazy::new(|| {
         );
 
         CREATE INDEX IF NOT EXISTS idx_attachments_message_id ON attachments(message_id);
+      "#;
+
+/// The initial schema without the vector table, for databases opened without sqlite_vec
+static INITIAL_SCHEMA_WITHOUT_VEC: Lazy<String> = Lazy::new(|| INITIAL_SCHEMA.replace(VEC_SCHEMA, ""));
+
+// Database schema migrations
+static MIGRATIONS: Lazy<Migrations<'static>> = Lazy::new(|| migrations_for(INITIAL_SCHEMA));
+
+static MIGRATIONS_WITHOUT_VEC: Lazy<Migrations<'static>> =
+  Lazy::new(|| migrations_for(&INITIAL_SCHEMA_WITHOUT_VEC));
+
+fn migrations_for(initial_schema: &'static str) -> Migrations<'static> {
+  Migrations::new(vec![
+    M::up(initial_schema),
+    M::up(
+      r#"
+        -- Raw provider exchanges, recorded only when debug logging is enabled
+        CREATE TABLE IF NOT EXISTS llm_debug_log (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          conversation_id TEXT,
+          provider TEXT NOT NULL,
+          request TEXT NOT NULL,
+          response TEXT NOT NULL,
+          timestamp TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_llm_debug_log_conversation_id ON llm_debug_log(conversation_id);
+      "#,
+    ),
+    M::up(
+      r#"
+        -- Cached "previously..." summary, valid while message_count matches summary_message_count
+        ALTER TABLE conversations ADD COLUMN summary TEXT;
+        ALTER TABLE conversations ADD COLUMN summary_message_count INTEGER;
+      "#,
+    ),
+    M::up(
+      r#"
+        -- Per-conversation streaming preference, NULL means the default (streaming on)
+        ALTER TABLE conversations ADD COLUMN stream_responses INTEGER;
+      "#,
+    ),
+    M::up(
+      r#"
+        -- Token usage for the Ollama provider
+        INSERT OR IGNORE INTO models (model) VALUES ('ollama');
       "#,
-  )])
-});
+    ),
+    M::up(
+      r#"
+        -- Full-text index over message content, kept in sync by triggers. Messages have a TEXT
+        -- primary key and an unstable implicit rowid, so each gets a fixed integer key here.
+        CREATE TABLE IF NOT EXISTS conversation_messages_fts_ids (
+          fts_rowid INTEGER PRIMARY KEY,
+          message_id TEXT NOT NULL UNIQUE
+        );
+
+        INSERT INTO conversation_messages_fts_ids (message_id) SELECT id FROM conversation_messages;
+
+        -- The index reads content through this view instead of keeping its own copy
+        CREATE VIEW IF NOT EXISTS conversation_messages_fts_content AS
+          SELECT ids.fts_rowid, m.content
+          FROM conversation_messages_fts_ids AS ids
+          JOIN conversation_messages AS m ON m.id = ids.message_id;
 
-fn get_db_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+        CREATE VIRTUAL TABLE IF NOT EXISTS conversation_messages_fts USING fts5(
+          content,
+          content='conversation_messages_fts_content',
+          content_rowid='fts_rowid'
+        );
+
+        INSERT INTO conversation_messages_fts(conversation_messages_fts) VALUES ('rebuild');
+
+        CREATE TRIGGER IF NOT EXISTS conversation_messages_fts_insert AFTER INSERT ON conversation_messages BEGIN
+          INSERT INTO conversation_messages_fts_ids (message_id) VALUES (new.id);
+          INSERT INTO conversation_messages_fts(rowid, content)
+            VALUES ((SELECT fts_rowid FROM conversation_messages_fts_ids WHERE message_id = new.id), new.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS conversation_messages_fts_delete AFTER DELETE ON conversation_messages BEGIN
+          INSERT INTO conversation_messages_fts(conversation_messages_fts, rowid, content)
+            VALUES ('delete', (SELECT fts_rowid FROM conversation_messages_fts_ids WHERE message_id = old.id), old.content);
+          DELETE FROM conversation_messages_fts_ids WHERE message_id = old.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS conversation_messages_fts_update AFTER UPDATE OF id, content ON conversation_messages BEGIN
+          INSERT INTO conversation_messages_fts(conversation_messages_fts, rowid, content)
+            VALUES ('delete', (SELECT fts_rowid FROM conversation_messages_fts_ids WHERE message_id = old.id), old.content);
+          UPDATE conversation_messages_fts_ids SET message_id = new.id WHERE message_id = old.id;
+          INSERT INTO conversation_messages_fts(rowid, content)
+            VALUES ((SELECT fts_rowid FROM conversation_messages_fts_ids WHERE message_id = new.id), new.content);
+        END;
+      "#,
+    ),
+    M::up(
+      r#"
+        -- Pinned conversations list first, archived ones are hidden by default
+        ALTER TABLE conversations ADD COLUMN is_pinned INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE conversations ADD COLUMN is_archived INTEGER NOT NULL DEFAULT 0;
+      "#,
+    ),
+    M::up(
+      r#"
+        -- Token usage per conversation, alongside the global token_usage totals
+        CREATE TABLE IF NOT EXISTS conversation_usage (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          conversation_id TEXT NOT NULL,
+          model TEXT NOT NULL,
+          provider TEXT NOT NULL,
+          prompt_tokens INTEGER NOT NULL,
+          completion_tokens INTEGER NOT NULL,
+          timestamp TEXT NOT NULL,
+          FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_conversation_usage_conversation_id ON conversation_usage(conversation_id);
+      "#,
+    ),
+  ])
+}
+
+pub fn get_db_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
   let app_data_path = app_handle
     .path()
     .app_data_dir()
@@ -123,37 +337,126 @@ fn get_db_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
   Ok(app_data_path.join("database.sqlite"))
 }
 
+/// The database file along with the write-ahead log and shared memory files WAL mode keeps beside it
+pub(crate) fn database_files(db_path: &Path) -> Vec<PathBuf> {
+  ["", "-wal", "-shm"]
+    .iter()
+    .map(|suffix| {
+      let mut path = db_path.as_os_str().to_owned();
+      path.push(suffix);
+      PathBuf::from(path)
+    })
+    .collect()
+}
+
+/// Use write-ahead logging so reads don't block on a writer, and wait out short locks
+/// instead of failing with "database is locked".
+fn configure_connection(conn: &Connection) -> Result<(), String> {
+  let journal_mode: String = conn
+    .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))
+    .map_err(|e| format!("Failed to enable WAL mode: {}", e))?;
+  if !journal_mode.eq_ignore_ascii_case("wal") {
+    log::warn!("[db] WAL mode is unavailable, using journal mode {}", journal_mode);
+  }
+  conn
+    .busy_timeout(DB_BUSY_TIMEOUT)
+    .map_err(|e| format!("Failed to set busy timeout: {}", e))
+}
+
+/// Register sqlite_vec for every new connection. Returns false if registration failed.
+pub(crate) fn register_vec_extension() -> bool {
+  let rc = unsafe {
+    sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())))
+  };
+  if rc != 0 {
+    log::warn!(
+      "[db] Failed to register sqlite_vec extension (SQLite error code: {}). Vector search will be unavailable.",
+      rc
+    );
+    return false;
+  }
+  log::info!("[db] Registered sqlite_vec extension");
+  true
+}
+
+/// Whether the sqlite_vec extension loaded and vector search can be used.
+pub fn is_vec_available() -> bool {
+  VEC_AVAILABLE.load(Ordering::SeqCst)
+}
+
 /// Initializes the SQLite database connection, registers extensions, and runs migrations.
-pub fn initialize_database(app_handle: &tauri::AppHandle) -> Result<Connection, String> {
-  let db_path = get_db_path(app_handle)?;
-
-  unsafe {
-    let rc = sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
-    if rc != 0 {
-      return Err(format!(
-        "Failed to register sqlite_vec extension. SQLite error code: {}",
-        rc
-      ));
+/// If sqlite_vec can't be loaded the database still opens, with vector search disabled.
+pub fn initialize_database(app_handle: &tauri::AppHandle) -> Result<Connection, DbError> {
+  let db_path = get_db_path(app_handle).map_err(DbError::Other)?;
+
+  let mut vec_available = register_vec_extension();
+
+  let mut conn = match Connection::open(&db_path) {
+    Ok(conn) => conn,
+    Err(e) if vec_available => {
+      // The extension's init runs on open, so a broken extension fails here. Retry without it.
+      log::warn!(
+        "[db] Failed to open database with sqlite_vec ({}). Retrying without vector search.",
+        e
+      );
+      unsafe { sqlite3_reset_auto_extension() };
+      vec_available = false;
+      Connection::open(&db_path)
+        .map_err(|e| DbError::sqlite("Failed to open database connection", e))?
     }
+    Err(e) => return Err(DbError::sqlite("Failed to open database connection", e)),
+  };
+
+  if vec_available && conn.query_row("SELECT vec_version()", [], |_| Ok(())).is_err() {
+    log::warn!("[db] sqlite_vec is registered but not usable. Vector search will be unavailable.");
+    vec_available = false;
   }
-  log::info!("[db] Registered sqlite_vec extension");
+  VEC_AVAILABLE.store(vec_available, Ordering::SeqCst);
 
-  let mut conn =
-    Connection::open(&db_path).map_err(|e| format!("Failed to open database connection: {}", e))?;
+  configure_connection(&conn).map_err(DbError::Other)?;
+  prepare_connection(&mut conn, vec_available)?;
+
+  Ok(conn)
+}
+
+/// Run migrations and create the vector table when sqlite_vec is available.
+pub(crate) fn prepare_connection(conn: &mut Connection, vec_available: bool) -> Result<(), DbError> {
+  // Migrating a database from a newer build could drop data it relies on, so refuse to open it
+  let migrations = if vec_available { &MIGRATIONS } else { &MIGRATIONS_WITHOUT_VEC };
+  if let Ok(SchemaVersion::Outside(version)) = migrations.current_version(conn) {
+    log::error!(
+      "[db] Database schema version {} is ahead of the latest known migration. Was it opened by a newer build?",
+      version
+    );
+    return Err(DbError::SchemaTooNew(version.get() as u32));
+  }
 
   log::info!("[db] Applying database migrations...");
-  MIGRATIONS.to_latest(&mut conn).map_err(|e| match e {
+  migrations.to_latest(conn).map_err(|e| match e {
     rusqlite_migration::Error::RusqliteError { query: _, err } => {
-      format!("SQLite error during migration: {}", err)
+      DbError::sqlite("SQLite error during migration", err)
     }
     rusqlite_migration::Error::MigrationDefinition(def_err) => {
-      format!("Migration definition error: {}", def_err)
+      DbError::Other(format!("Migration definition error: {}", def_err))
     }
-    other => format!("Unknown migration error: {}", other),
+    other => DbError::Other(format!("Unknown migration error: {}", other)),
   })?;
   log::info!("[db] Migrations applied successfully.");
 
-  Ok(conn)
+  if vec_available {
+    conn
+      .execute_batch(VEC_SCHEMA)
+      .map_err(|e| DbError::sqlite("Failed to create vector table", e))?;
+    // Memories saved while sqlite_vec was unavailable have no vector entry yet
+    let backfilled = crate::db::memory::backfill_memory_embeddings(conn).map_err(DbError::Other)?;
+    if backfilled > 0 {
+      log::info!("[db] Added {} memories to the vector index", backfilled);
+    }
+  } else {
+    log::warn!("[db] Skipping vector table creation; sqlite_vec is unavailable.");
+  }
+
+  Ok(())
 }
 
 // Helper to convert rusqlite ValueRef to serde_json Value
@@ -215,6 +518,22 @@ pub fn bytes_to_f32_vec(blob: &[u8]) -> Result<Vec<f32>, String> {
   Ok(out)
 }
 
+/// Get the schema version (SQLite user_version) of the open database
+#[tauri::command]
+pub fn get_schema_version(state: tauri::State<DbState>) -> Result<u32, String> {
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  conn
+    .pragma_query_value(None, "user_version", |row| row.get(0))
+    .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
 /// Executes an arbitrary SQL command. For dev/debug purposes.
 #[tauri::command]
 pub fn execute_sql(
@@ -309,13 +628,16 @@ pub fn reset_database(
     log::info!("[db] Closed existing database connection.");
   }
 
-  log::info!("[db] Deleting database file: {:?}", db_path);
-  match fs::remove_file(&db_path) {
-    Ok(_) => log::info!("[db] Database file deleted successfully."),
-    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-      log::debug!("[db] Database file not found, skipping deletion.")
+  // A leftover write-ahead log would be replayed into the fresh database
+  for path in database_files(&db_path) {
+    log::info!("[db] Deleting database file: {:?}", path);
+    match fs::remove_file(&path) {
+      Ok(_) => log::info!("[db] Database file deleted successfully."),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        log::debug!("[db] Database file not found, skipping deletion.")
+      }
+      Err(e) => return Err(format!("Failed to delete database file: {}", e)),
     }
-    Err(e) => return Err(format!("Failed to delete database file: {}", e)),
   }
 
   log::info!("[db] Re-initializing database...");
@@ -335,3 +657,93 @@ pub fn reset_database(
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_database_initializes_without_sqlite_vec() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    prepare_connection(&mut conn, false).unwrap();
+
+    let tables: Vec<String> = conn
+      .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+      .unwrap()
+      .query_map([], |row| row.get(0))
+      .unwrap()
+      .collect::<Result<_, _>>()
+      .unwrap();
+    assert!(tables.contains(&"conversations".to_string()));
+    assert!(tables.contains(&"memory_entries".to_string()));
+    assert!(!tables.contains(&"memory_entries_vec".to_string()));
+    assert_ne!(INITIAL_SCHEMA_WITHOUT_VEC.as_str(), INITIAL_SCHEMA);
+  }
+
+  #[test]
+  fn test_file_database_uses_wal_and_busy_timeout() {
+    let db_path = std::env::temp_dir().join(format!("ambient-db-{}.sqlite", uuid::Uuid::new_v4()));
+    let mut conn = Connection::open(&db_path).unwrap();
+    configure_connection(&conn).unwrap();
+    prepare_connection(&mut conn, false).unwrap();
+
+    let journal_mode: String = conn.pragma_query_value(None, "journal_mode", |row| row.get(0)).unwrap();
+    let busy_timeout: u64 = conn.pragma_query_value(None, "busy_timeout", |row| row.get(0)).unwrap();
+    let wal_exists = database_files(&db_path)[1].exists();
+    drop(conn);
+    for path in database_files(&db_path) {
+      let _ = fs::remove_file(path);
+    }
+
+    assert_eq!(journal_mode, "wal");
+    assert_eq!(busy_timeout, DB_BUSY_TIMEOUT.as_millis() as u64);
+    assert!(wal_exists);
+  }
+
+  #[test]
+  fn test_database_from_newer_build_is_rejected() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    prepare_connection(&mut conn, false).unwrap();
+    let version: u32 = conn
+      .pragma_query_value(None, "user_version", |row| row.get(0))
+      .unwrap();
+    assert!(version > 0);
+
+    // Reopening at the same version is fine
+    prepare_connection(&mut conn, false).unwrap();
+
+    conn.pragma_update(None, "user_version", version + 1).unwrap();
+    let err = prepare_connection(&mut conn, false).unwrap_err();
+    assert!(matches!(err, DbError::SchemaTooNew(v) if v == version + 1));
+  }
+
+  #[test]
+  fn test_sqlite_errors_are_classified() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    prepare_connection(&mut conn, false).unwrap();
+
+    let missing = conn
+      .query_row("SELECT id FROM conversations WHERE id = 'missing'", [], |row| {
+        row.get::<_, String>(0)
+      })
+      .unwrap_err();
+    let err = DbError::sqlite("Failed to get conversation", missing);
+    assert!(matches!(err, DbError::NotFound(_)));
+    assert!(String::from(err).starts_with("Failed to get conversation: "));
+
+    // Messages must belong to a conversation
+    let orphan = conn
+      .execute(
+        "INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp)
+           VALUES ('m1', 'missing', 'user', 'hi', '')",
+        [],
+      )
+      .unwrap_err();
+    assert!(matches!(DbError::from(orphan), DbError::Constraint(_)));
+
+    assert_eq!(
+      serde_json::to_value(DbError::Locked).unwrap(),
+      serde_json::json!({ "kind": "Locked" })
+    );
+  }
+}
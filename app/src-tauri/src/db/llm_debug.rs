@@ -0,0 +1,308 @@
+use crate::constants::LLM_DEBUG_LOG_LIMIT;
+use crate::db::core::DbState;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use ts_rs::TS;
+
+/// Keys whose values are replaced before an exchange is persisted. Keys are compared
+/// with case, `_` and `-` ignored, so `api_key` matches but `max_tokens` does not.
+const SECRET_KEYS: [&str; 9] = [
+  "token",
+  "accesstoken",
+  "refreshtoken",
+  "apikey",
+  "xapikey",
+  "authorization",
+  "password",
+  "secret",
+  "clientsecret",
+];
+const REDACTED: &str = "[REDACTED]";
+
+/// A raw request/response pair sent to an LLM provider
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "llm.ts")]
+pub struct LlmExchange {
+  pub conversation_id: Option<String>,
+  pub provider: String,
+  #[ts(type = "any")]
+  pub request: Value,
+  pub response: String,
+  pub timestamp: String,
+}
+
+/// Record a provider exchange if debug logging is enabled. Failures are only logged
+/// so debugging can never break generation.
+pub async fn record_llm_exchange(
+  app_handle: &AppHandle,
+  conversation_id: Option<String>,
+  provider: &str,
+  request: &Value,
+  response: &str,
+) {
+  let enabled = crate::settings::service::load_user_settings(app_handle.clone())
+    .await
+    .map(|s| s.llm_debug_logging)
+    .unwrap_or(false);
+  if !enabled {
+    return;
+  }
+
+  let state = app_handle.state::<DbState>();
+  let Ok(conn_guard) = state.0.lock() else {
+    log::warn!("[llm_debug] Failed to acquire DB lock");
+    return;
+  };
+  let Some(conn) = conn_guard.as_ref() else {
+    return;
+  };
+
+  let exchange = LlmExchange {
+    conversation_id,
+    provider: provider.to_string(),
+    request: request.clone(),
+    response: response.to_string(),
+    timestamp: Utc::now().to_rfc3339(),
+  };
+  if let Err(e) = insert_exchange(conn, exchange) {
+    log::warn!("[llm_debug] Failed to record exchange: {}", e);
+  }
+}
+
+/// Get the most recent recorded exchange for a conversation
+#[tauri::command]
+pub fn get_last_llm_exchange(
+  app_handle: AppHandle,
+  conversation_id: String,
+) -> Result<Option<LlmExchange>, String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  query_last_exchange(conn, &conversation_id)
+}
+
+/// Drop everything recorded so far, once the user turns debug logging off
+pub fn clear_llm_exchanges(app_handle: &AppHandle) -> Result<(), String> {
+  let state = app_handle.state::<DbState>();
+  let conn_guard = state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let Some(conn) = conn_guard.as_ref() else {
+    return Ok(());
+  };
+  delete_exchanges(conn, None)
+}
+
+/// Delete the recorded exchanges of one conversation, or all of them
+pub(crate) fn delete_exchanges(
+  conn: &Connection,
+  conversation_id: Option<&str>,
+) -> Result<(), String> {
+  conn
+    .execute(
+      "DELETE FROM llm_debug_log WHERE ?1 IS NULL OR conversation_id = ?1",
+      params![conversation_id],
+    )
+    .map_err(|e| format!("Failed to delete debug log: {}", e))?;
+  Ok(())
+}
+
+fn insert_exchange(conn: &Connection, mut exchange: LlmExchange) -> Result<(), String> {
+  redact_secrets(&mut exchange.request);
+  let response = match serde_json::from_str::<Value>(&exchange.response) {
+    Ok(mut value) => {
+      redact_secrets(&mut value);
+      value.to_string()
+    }
+    Err(_) => exchange.response,
+  };
+
+  conn
+    .execute(
+      "INSERT INTO llm_debug_log (conversation_id, provider, request, response, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+      params![
+        exchange.conversation_id,
+        exchange.provider,
+        exchange.request.to_string(),
+        response,
+        exchange.timestamp,
+      ],
+    )
+    .map_err(|e| format!("Failed to insert exchange: {}", e))?;
+
+  // Keep the log bounded
+  conn
+    .execute(
+      "DELETE FROM llm_debug_log WHERE id NOT IN
+         (SELECT id FROM llm_debug_log ORDER BY id DESC LIMIT ?1)",
+      params![LLM_DEBUG_LOG_LIMIT],
+    )
+    .map_err(|e| format!("Failed to trim debug log: {}", e))?;
+
+  Ok(())
+}
+
+fn query_last_exchange(
+  conn: &Connection,
+  conversation_id: &str,
+) -> Result<Option<LlmExchange>, String> {
+  conn
+    .query_row(
+      "SELECT conversation_id, provider, request, response, timestamp
+         FROM llm_debug_log
+         WHERE conversation_id = ?1
+         ORDER BY id DESC
+         LIMIT 1",
+      params![conversation_id],
+      |row| {
+        let request: String = row.get(2)?;
+        Ok(LlmExchange {
+          conversation_id: row.get(0)?,
+          provider: row.get(1)?,
+          request: serde_json::from_str(&request).unwrap_or(Value::String(request)),
+          response: row.get(3)?,
+          timestamp: row.get(4)?,
+        })
+      },
+    )
+    .optional()
+    .map_err(|e| format!("Failed to query debug log: {}", e))
+}
+
+/// Replace the values of secret keys anywhere in a JSON value, and inline images with
+/// their size so screenshots don't bloat the log
+fn redact_secrets(value: &mut Value) {
+  match value {
+    Value::Object(map) => {
+      for (key, val) in map.iter_mut() {
+        if is_secret_key(key) && !val.is_number() {
+          *val = Value::String(REDACTED.to_string());
+        } else if key == "inlineData" {
+          // Gemini-style parts keep the raw base64 under "data"
+          if let Some(data) = val.get_mut("data") {
+            if let Some(len) = data.as_str().map(str::len) {
+              *data = Value::String(base64_placeholder(len));
+            }
+          }
+        } else {
+          redact_secrets(val);
+        }
+      }
+    }
+    Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+    Value::String(text) => {
+      if let Some(stripped) = strip_data_url(text) {
+        *text = stripped;
+      }
+    }
+    _ => {}
+  }
+}
+
+fn is_secret_key(key: &str) -> bool {
+  let normalized: String = key
+    .chars()
+    .filter(|c| *c != '_' && *c != '-')
+    .collect::<String>()
+    .to_lowercase();
+  SECRET_KEYS.contains(&normalized.as_str())
+}
+
+/// Shorten a base64 data URL to its media type and size
+fn strip_data_url(text: &str) -> Option<String> {
+  let rest = text.strip_prefix("data:")?;
+  let (media_type, data) = rest.split_once(";base64,")?;
+  Some(format!("data:{};base64,{}", media_type, base64_placeholder(data.len())))
+}
+
+fn base64_placeholder(encoded_len: usize) -> String {
+  format!("[{} bytes omitted]", encoded_len / 4 * 3)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_exchange_is_captured_with_secrets_redacted() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+
+    let exchange = LlmExchange {
+      conversation_id: Some("conv-1".to_string()),
+      provider: "fast".to_string(),
+      request: json!({
+        "modelType": "fast",
+        "token": "eyJhbGciOi.secret",
+        "max_tokens": 512,
+        "chat_template_kwargs": { "enable_thinking": false },
+        "headers": { "Authorization": "Bearer abc", "X-Api-Key": "xyz" },
+        "content": [{ "role": "user", "parts": [
+          { "text": "hello" },
+          { "inlineData": { "mimeType": "image/png", "data": "QUJD".repeat(1000) } }
+        ] }],
+        "messages": [{ "role": "user", "content": [
+          { "type": "image_url", "image_url": { "url": format!("data:image/jpeg;base64,{}", "QUJD".repeat(10)) } }
+        ] }]
+      }),
+      response: "hi there".to_string(),
+      timestamp: Utc::now().to_rfc3339(),
+    };
+    insert_exchange(&conn, exchange).unwrap();
+
+    let last = query_last_exchange(&conn, "conv-1").unwrap().unwrap();
+    assert_eq!(last.request["token"], REDACTED);
+    assert_eq!(last.request["modelType"], "fast");
+    assert_eq!(last.request["content"][0]["parts"][0]["text"], "hello");
+    assert_eq!(last.request["max_tokens"], 512);
+    assert_eq!(last.request["chat_template_kwargs"]["enable_thinking"], false);
+    assert_eq!(last.request["headers"]["Authorization"], REDACTED);
+    assert_eq!(last.request["headers"]["X-Api-Key"], REDACTED);
+    assert_eq!(
+      last.request["content"][0]["parts"][1]["inlineData"],
+      json!({ "mimeType": "image/png", "data": "[3000 bytes omitted]" })
+    );
+    assert_eq!(
+      last.request["messages"][0]["content"][0]["image_url"]["url"],
+      "data:image/jpeg;base64,[30 bytes omitted]"
+    );
+    assert_eq!(last.response, "hi there");
+    assert!(!last.request.to_string().contains("eyJhbGciOi"));
+
+    assert!(query_last_exchange(&conn, "conv-2").unwrap().is_none());
+  }
+
+  #[test]
+  fn test_exchanges_are_deleted_per_conversation_and_in_full() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    let exchange = |conversation_id: &str| LlmExchange {
+      conversation_id: Some(conversation_id.to_string()),
+      provider: "local".to_string(),
+      request: json!({ "prompt": "hello" }),
+      response: "hi".to_string(),
+      timestamp: Utc::now().to_rfc3339(),
+    };
+    insert_exchange(&conn, exchange("conv-1")).unwrap();
+    insert_exchange(&conn, exchange("conv-2")).unwrap();
+
+    delete_exchanges(&conn, Some("conv-1")).unwrap();
+    assert!(query_last_exchange(&conn, "conv-1").unwrap().is_none());
+    assert!(query_last_exchange(&conn, "conv-2").unwrap().is_some());
+
+    delete_exchanges(&conn, None).unwrap();
+    assert!(query_last_exchange(&conn, "conv-2").unwrap().is_none());
+  }
+}
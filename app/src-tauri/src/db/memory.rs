@@ -1,5 +1,7 @@
-use crate::db::core::DbState;
-use crate::memory::types::MemoryEntry;
+use crate::constants::MEMORY_RERANK_CANDIDATE_FACTOR;
+use crate::db::core::{is_vec_available, DbState, VEC_UNAVAILABLE_ERROR};
+use crate::memory::types::{MemoryEntry, MemoryRankWeights, RankedMemory};
+use chrono::{DateTime, Utc};
 use crate::models::embedding::embedding::generate_embedding;
 use rusqlite::params;
 use rusqlite::OptionalExtension;
@@ -42,12 +44,25 @@ pub fn insert_memory_entry(state: State<DbState>, memory_entry: MemoryEntry) ->
     .map_err(|e| format!("Failed to insert memory entry: {}", e))?;
 
   // Also insert into sqlite-vec virtual table for similarity search (tables created via migrations)
+  if !is_vec_available() {
+    log::warn!("[memory] sqlite_vec unavailable, memory saved without a vector index entry");
+    return Ok(());
+  }
+
+  upsert_memory_embedding(conn, &memory_entry.id, &memory_entry.embedding)
+}
 
+/// Store a memory's embedding in the vector index, replacing any previous one
+fn upsert_memory_embedding(
+  conn: &rusqlite::Connection,
+  memory_id: &str,
+  embedding: &[f32],
+) -> Result<(), String> {
   // Insert mapping row to obtain rowid
   conn
     .execute(
       "INSERT OR IGNORE INTO memory_entry_vec_map(memory_id) VALUES (?1)",
-      rusqlite::params![memory_entry.id],
+      rusqlite::params![memory_id],
     )
     .map_err(|e| format!("Failed to insert mapping row: {}", e))?;
 
@@ -55,7 +70,7 @@ pub fn insert_memory_entry(state: State<DbState>, memory_entry: MemoryEntry) ->
   let rowid: i64 = conn
     .query_row(
       "SELECT rowid FROM memory_entry_vec_map WHERE memory_id = ?1",
-      rusqlite::params![memory_entry.id],
+      rusqlite::params![memory_id],
       |row| row.get(0),
     )
     .map_err(|e| format!("Failed to fetch mapping rowid: {}", e))?;
@@ -65,7 +80,7 @@ pub fn insert_memory_entry(state: State<DbState>, memory_entry: MemoryEntry) ->
   let updated = conn
     .execute(
       "UPDATE memory_entries_vec SET embedding = ?1 WHERE rowid = ?2",
-      rusqlite::params![memory_entry.embedding.as_bytes(), rowid],
+      rusqlite::params![embedding.as_bytes(), rowid],
     )
     .map_err(|e| format!("Failed to update embedding in memory_entries_vec: {}", e))?;
 
@@ -73,7 +88,7 @@ pub fn insert_memory_entry(state: State<DbState>, memory_entry: MemoryEntry) ->
     conn
       .execute(
         "INSERT INTO memory_entries_vec(rowid, embedding) VALUES (?1, ?2)",
-        rusqlite::params![rowid, memory_entry.embedding.as_bytes()],
+        rusqlite::params![rowid, embedding.as_bytes()],
       )
       .map_err(|e| format!("Failed to insert embedding into memory_entries_vec: {}", e))?;
   }
@@ -81,6 +96,31 @@ pub fn insert_memory_entry(state: State<DbState>, memory_entry: MemoryEntry) ->
   Ok(())
 }
 
+/// Add memories that have no vector index entry, e.g. ones saved while sqlite_vec was
+/// unavailable. Returns how many were added.
+pub(crate) fn backfill_memory_embeddings(conn: &rusqlite::Connection) -> Result<usize, String> {
+  let mut stmt = conn
+    .prepare(
+      "SELECT id, embedding FROM memory_entries
+         WHERE id NOT IN (SELECT memory_id FROM memory_entry_vec_map)",
+    )
+    .map_err(|e| format!("Failed to prepare backfill query: {}", e))?;
+  let missing = stmt
+    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)))
+    .map_err(|e| format!("Failed to query memories to backfill: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Failed to read memories to backfill: {}", e))?;
+
+  for (id, bytes) in &missing {
+    let embedding: Vec<f32> = bytes
+      .chunks_exact(4)
+      .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+      .collect();
+    upsert_memory_embedding(conn, id, &embedding)?;
+  }
+  Ok(missing.len())
+}
+
 /// Retrieves memory entries from the database with pagination.
 #[tauri::command]
 pub fn get_memory_entries(
@@ -205,6 +245,68 @@ pub fn get_memory_entries_with_message(
   Ok(serde_json::Value::Array(rows))
 }
 
+/// Retrieves memory entries filtered by type and time range, newest first.
+/// Timestamps are RFC 3339 strings and bounds are inclusive.
+#[tauri::command]
+pub fn get_memories(
+  app_handle: tauri::AppHandle,
+  memory_type: Option<String>,
+  start_ts: Option<String>,
+  end_ts: Option<String>,
+  limit: u32,
+) -> Result<Vec<MemoryEntry>, String> {
+  let db_state = app_handle.state::<DbState>();
+  let conn_guard = db_state
+    .0
+    .lock()
+    .map_err(|_| "Failed to acquire DB lock".to_string())?;
+  let conn = conn_guard
+    .as_ref()
+    .ok_or("Database connection not available.".to_string())?;
+
+  query_memories(conn, memory_type, start_ts, end_ts, limit)
+}
+
+fn query_memories(
+  conn: &rusqlite::Connection,
+  memory_type: Option<String>,
+  start_ts: Option<String>,
+  end_ts: Option<String>,
+  limit: u32,
+) -> Result<Vec<MemoryEntry>, String> {
+  let sql = r#"
+		SELECT id, message_id, memory_type, text, timestamp
+		FROM memory_entries
+		WHERE (?1 IS NULL OR memory_type = ?1)
+		  AND (?2 IS NULL OR timestamp >= ?2)
+		  AND (?3 IS NULL OR timestamp <= ?3)
+		ORDER BY timestamp DESC
+		LIMIT ?4
+	"#;
+
+  let mut stmt = conn
+    .prepare(sql)
+    .map_err(|e| format!("Prepare failed: {}", e))?;
+
+  let entries = stmt
+    .query_map(params![memory_type, start_ts, end_ts, limit], |row| {
+      Ok(MemoryEntry {
+        id: row.get(0)?,
+        message_id: row.get(1)?,
+        memory_type: row.get(2)?,
+        text: row.get(3)?,
+        embedding: Vec::new(), // Do not return embedding for efficiency
+        timestamp: row.get(4)?,
+        similarity: None,
+      })
+    })
+    .map_err(|e| format!("Query map failed: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Row processing failed: {}", e))?;
+
+  Ok(entries)
+}
+
 /// Deletes a single memory entry and cleans up any vector index mappings.
 #[tauri::command]
 pub fn delete_memory_entry(state: State<DbState>, id: String) -> Result<(), String> {
@@ -230,7 +332,8 @@ pub fn delete_memory_entry(state: State<DbState>, id: String) -> Result<(), Stri
     .optional()
     .map_err(|e| format!("Failed to fetch mapping rowid: {}", e))?;
 
-  if let Some(rid) = rowid {
+  // The vector table only exists when sqlite_vec is loaded
+  if let Some(rid) = rowid.filter(|_| is_vec_available()) {
     tx.execute(
       "DELETE FROM memory_entries_vec WHERE rowid = ?1",
       params![rid],
@@ -268,8 +371,10 @@ pub fn delete_all_memories(state: State<DbState>) -> Result<(), String> {
     .transaction()
     .map_err(|e| format!("Failed to start transaction: {}", e))?;
 
-  tx.execute("DELETE FROM memory_entries_vec", [])
-    .map_err(|e| format!("Failed to clear memory_entries_vec: {}", e))?;
+  if is_vec_available() {
+    tx.execute("DELETE FROM memory_entries_vec", [])
+      .map_err(|e| format!("Failed to clear memory_entries_vec: {}", e))?;
+  }
   tx.execute("DELETE FROM memory_entry_vec_map", [])
     .map_err(|e| format!("Failed to clear memory_entry_vec_map: {}", e))?;
   tx.execute("DELETE FROM memory_entries", [])
@@ -281,12 +386,69 @@ pub fn delete_all_memories(state: State<DbState>) -> Result<(), String> {
   Ok(())
 }
 
+/// Semantic search over stored memories, most similar first
+#[tauri::command]
+pub async fn search_memories(
+  app_handle: tauri::AppHandle,
+  query: String,
+  limit: u32,
+  weights: Option<MemoryRankWeights>,
+) -> Result<Vec<RankedMemory>, String> {
+  // Cosine similarity is never below -1, so no candidate is filtered out
+  let candidates = find_similar_memories(
+    &app_handle,
+    &query,
+    limit.saturating_mul(MEMORY_RERANK_CANDIDATE_FACTOR),
+    -1.0,
+  )
+  .await?;
+
+  let mut ranked = rerank_memories(candidates, &weights.unwrap_or_default(), Utc::now());
+  ranked.truncate(limit as usize);
+  Ok(ranked)
+}
+
+/// Score memories by similarity plus a recency bonus that halves every
+/// `half_life_days`, plus any boost for their type. Ties are broken by id.
+fn rerank_memories(
+  memories: Vec<MemoryEntry>,
+  weights: &MemoryRankWeights,
+  now: DateTime<Utc>,
+) -> Vec<RankedMemory> {
+  let mut ranked: Vec<RankedMemory> = memories
+    .into_iter()
+    .map(|memory| {
+      // Unparseable timestamps get no recency bonus
+      let recency = DateTime::parse_from_rfc3339(&memory.timestamp)
+        .map(|t| {
+          let age_days = (now - t.with_timezone(&Utc)).num_seconds().max(0) as f64 / 86_400.0;
+          0.5f64.powf(age_days / weights.half_life_days.max(f64::EPSILON))
+        })
+        .unwrap_or(0.0);
+      let boost = weights.type_boosts.get(&memory.memory_type).copied().unwrap_or(0.0);
+      let score = memory.similarity.unwrap_or(0.0) + weights.recency * recency + boost;
+      RankedMemory { memory, score }
+    })
+    .collect();
+
+  ranked.sort_by(|a, b| {
+    b.score
+      .total_cmp(&a.score)
+      .then_with(|| a.memory.id.cmp(&b.memory.id))
+  });
+  ranked
+}
+
 pub async fn find_similar_memories(
   app_handle: &tauri::AppHandle,
   prompt: &str,
   k: u32,
   p: f32,
 ) -> Result<Vec<MemoryEntry>, String> {
+  if !is_vec_available() {
+    return Err(VEC_UNAVAILABLE_ERROR.to_string());
+  }
+
   // Generate query embedding for the prompt
   let query_embedding: Vec<f32> = generate_embedding(app_handle.clone(), prompt.to_string())
     .await
@@ -301,6 +463,16 @@ pub async fn find_similar_memories(
     .as_ref()
     .ok_or_else(|| "Database connection not available".to_string())?;
 
+  query_similar_memories(conn, &query_embedding, k, p)
+}
+
+/// Top-k memories by cosine similarity to an embedding, keeping those at or above `p`
+fn query_similar_memories(
+  conn: &rusqlite::Connection,
+  query_embedding: &[f32],
+  k: u32,
+  p: f32,
+) -> Result<Vec<MemoryEntry>, String> {
   // Query using cosine distance function directly
   // Join through the mapping table to fetch full memory entries (including embedding BLOB).
   let sql = r#"
@@ -370,3 +542,175 @@ pub async fn find_similar_memories(
 
   Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rusqlite::Connection;
+
+  fn seed_connection() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute_batch(
+        r#"
+        INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('c', 'Chat', '', '');
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp) VALUES
+          ('m1', 'c', 'user', '', ''), ('m2', 'c', 'user', '', ''), ('m3', 'c', 'user', '', '');
+        INSERT INTO memory_entries (id, message_id, memory_type, text, embedding, timestamp) VALUES
+          ('a', 'm1', 'semantic', 'likes tea', x'', '2025-01-01T00:00:00+00:00'),
+          ('b', 'm2', 'episodic', 'went hiking', x'', '2025-01-02T00:00:00+00:00'),
+          ('c', 'm3', 'semantic', 'lives in Ohio', x'', '2025-01-03T00:00:00+00:00');
+        "#,
+      )
+      .unwrap();
+    conn
+  }
+
+  #[test]
+  fn test_query_memories_filters_by_type() {
+    let conn = seed_connection();
+
+    let semantic = query_memories(&conn, Some("semantic".to_string()), None, None, 10).unwrap();
+    let ids: Vec<&str> = semantic.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["c", "a"]);
+
+    let all = query_memories(&conn, None, None, None, 10).unwrap();
+    assert_eq!(all.len(), 3);
+  }
+
+  #[test]
+  fn test_query_memories_filters_by_time_range() {
+    let conn = seed_connection();
+
+    let ranged = query_memories(
+      &conn,
+      None,
+      Some("2025-01-02T00:00:00+00:00".to_string()),
+      Some("2025-01-02T23:59:59+00:00".to_string()),
+      10,
+    )
+    .unwrap();
+    assert_eq!(ranged.len(), 1);
+    assert_eq!(ranged[0].id, "b");
+  }
+
+  #[test]
+  fn test_newer_memory_can_outrank_a_slightly_more_similar_one() {
+    let now = DateTime::parse_from_rfc3339("2025-06-01T00:00:00+00:00")
+      .unwrap()
+      .with_timezone(&Utc);
+    let memory = |id: &str, memory_type: &str, timestamp: &str, similarity: f64| MemoryEntry {
+      id: id.to_string(),
+      message_id: "m".to_string(),
+      memory_type: memory_type.to_string(),
+      text: String::new(),
+      embedding: Vec::new(),
+      timestamp: timestamp.to_string(),
+      similarity: Some(similarity),
+    };
+    let memories = vec![
+      memory("old", "semantic", "2024-06-01T00:00:00+00:00", 0.85),
+      memory("new", "episodic", "2025-05-31T00:00:00+00:00", 0.80),
+    ];
+
+    let ranked = rerank_memories(memories.clone(), &MemoryRankWeights::default(), now);
+    let ids: Vec<&str> = ranked.iter().map(|r| r.memory.id.as_str()).collect();
+    assert_eq!(ids, ["new", "old"]);
+    assert_eq!(ranked[1].memory.similarity, Some(0.85));
+
+    // Without the recency term, similarity decides, and a type boost can flip it back
+    let similarity_only = MemoryRankWeights { recency: 0.0, ..Default::default() };
+    assert_eq!(rerank_memories(memories.clone(), &similarity_only, now)[0].memory.id, "old");
+    let boosted = MemoryRankWeights {
+      type_boosts: [("semantic".to_string(), 0.5)].into(),
+      ..Default::default()
+    };
+    assert_eq!(rerank_memories(memories, &boosted, now)[0].memory.id, "old");
+  }
+
+  #[test]
+  fn test_similar_memories_are_ordered_by_similarity() {
+    assert!(crate::db::core::register_vec_extension());
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, true).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('c', 'Chat', '', '');
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp)
+          VALUES ('m', 'c', 'user', 'hi', '');",
+      )
+      .unwrap();
+
+    // Unit vectors along the first two axes, blended in different amounts
+    let vector = |x: f32, y: f32| {
+      let mut v = vec![0.0f32; 768];
+      v[0] = x;
+      v[1] = y;
+      v
+    };
+    for (id, embedding) in [
+      ("far", vector(0.0, 1.0)),
+      ("near", vector(0.9, 0.1)),
+      ("exact", vector(1.0, 0.0)),
+    ] {
+      conn
+        .execute(
+          "INSERT INTO memory_entries (id, message_id, memory_type, text, embedding, timestamp)
+             VALUES (?1, 'm', 'semantic', ?1, x'', '')",
+          params![id],
+        )
+        .unwrap();
+      upsert_memory_embedding(&conn, id, &embedding).unwrap();
+    }
+
+    let results = query_similar_memories(&conn, &vector(1.0, 0.0), 3, -1.0).unwrap();
+    let ids: Vec<&str> = results.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["exact", "near", "far"]);
+    assert!((results[0].similarity.unwrap() - 1.0).abs() < 1e-5);
+
+    // Threshold and limit both apply
+    let close = query_similar_memories(&conn, &vector(1.0, 0.0), 3, 0.5).unwrap();
+    assert_eq!(close.len(), 2);
+    assert_eq!(query_similar_memories(&conn, &vector(1.0, 0.0), 1, -1.0).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn test_memories_saved_without_vec_are_backfilled() {
+    let db_path = std::env::temp_dir().join(format!("ambient-backfill-{}.sqlite", uuid::Uuid::new_v4()));
+    let mut conn = Connection::open(&db_path).unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    let mut embedding = vec![0.0f32; 768];
+    embedding[0] = 1.0;
+    let bytes: Vec<u8> = embedding.iter().flat_map(|f| f.to_le_bytes()).collect();
+    conn
+      .execute_batch(
+        "INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('c', 'Chat', '', '');
+        INSERT INTO conversation_messages (id, conversation_id, role, content, timestamp)
+          VALUES ('m', 'c', 'user', 'hi', '');",
+      )
+      .unwrap();
+    conn
+      .execute(
+        "INSERT INTO memory_entries (id, message_id, memory_type, text, embedding, timestamp)
+           VALUES ('saved-offline', 'm', 'semantic', 'likes tea', ?1, '')",
+        params![bytes],
+      )
+      .unwrap();
+
+    // Reopening with sqlite_vec creates the vector table and indexes the memory
+    drop(conn);
+    assert!(crate::db::core::register_vec_extension());
+    let mut conn = Connection::open(&db_path).unwrap();
+    crate::db::core::prepare_connection(&mut conn, true).unwrap();
+    let results = query_similar_memories(&conn, &embedding, 5, 0.5).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, "saved-offline");
+
+    // Already indexed memories are left alone
+    assert_eq!(backfill_memory_embeddings(&conn).unwrap(), 0);
+
+    drop(conn);
+    let _ = std::fs::remove_file(&db_path);
+  }
+}
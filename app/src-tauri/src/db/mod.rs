@@ -1,5 +1,7 @@
 pub mod conversations;
 pub mod core;
+pub mod llm_debug;
 pub mod memory;
+pub mod pagination;
 pub mod computer_use;
 pub mod token_usage;
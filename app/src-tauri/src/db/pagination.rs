@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// A single page of results along with the metadata needed to request the next one
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "pagination.ts")]
+pub struct Paginated<T: TS> {
+  pub items: Vec<T>,
+  pub total: u64,
+  pub offset: u64,
+  pub limit: u64,
+  pub has_more: bool,
+}
+
+impl<T: TS> Paginated<T> {
+  pub fn new(items: Vec<T>, total: u64, offset: u64, limit: u64) -> Self {
+    let has_more = offset + (items.len() as u64) < total;
+    Self {
+      items,
+      total,
+      offset,
+      limit,
+      has_more,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_has_more_at_page_boundary() {
+    // Second of two full pages: nothing left after it
+    let page = Paginated::new(vec![0u32; 10], 20, 10, 10);
+    assert!(!page.has_more);
+
+    // First of two full pages: one more page remains
+    let page = Paginated::new(vec![0u32; 10], 20, 0, 10);
+    assert!(page.has_more);
+
+    // One item past the boundary
+    let page = Paginated::new(vec![0u32; 10], 21, 10, 10);
+    assert!(page.has_more);
+  }
+}
@@ -1,12 +1,13 @@
 use crate::db::core::DbState;
 use chrono::{Utc, DateTime, Datelike, NaiveDate, NaiveDateTime};
-use rusqlite::params;
+use rusqlite::{params, Connection};
 use tauri::{AppHandle, Manager};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use std::collections::{HashSet, BTreeMap};
 use crate::constants::{COST_PER_TOKEN, WATER_PER_TOKEN, ENERGY_PER_TOKEN};
 use crate::events::{emitter::emit, types::{TOKEN_USAGE_CHANGED, TokenUsageChangedEvent}};
+use crate::settings::types::ModelPrice;
 
 /// Time filters for querying token usage
 #[derive(Debug, Serialize, Deserialize, TS, Clone, Copy, PartialEq, Eq)]
@@ -58,6 +59,28 @@ pub struct TokenUsageConsumptionResult {
   pub energy_unit: String,
 }
 
+/// Tokens one model used within a conversation
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "token_usage.ts")]
+pub struct ConversationModelUsage {
+  pub model: String,
+  pub provider: String,
+  pub prompt_tokens: u64,
+  pub completion_tokens: u64,
+  /// Estimated USD cost, zero for models without a price such as local ones
+  pub estimated_cost: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "token_usage.ts")]
+pub struct ConversationUsage {
+  pub conversation_id: String,
+  pub prompt_tokens: u64,
+  pub completion_tokens: u64,
+  pub estimated_cost: f64,
+  pub models: Vec<ConversationModelUsage>,
+}
+
 /// Add token usage record
 pub async fn add_token_usage(
   app_handle: AppHandle,
@@ -384,4 +407,165 @@ pub async fn get_token_usage(
     total_completion_tokens,
     time_range,
   })
-}
\ No newline at end of file
+}
+
+/// Record the tokens a generation used in its conversation. Failures are only logged
+/// so usage tracking can never break generation.
+pub async fn record_conversation_usage(
+  app_handle: &AppHandle,
+  conversation_id: Option<String>,
+  provider: &str,
+  model: &str,
+  prompt_tokens: u64,
+  completion_tokens: u64,
+) {
+  let Some(conversation_id) = conversation_id else {
+    return;
+  };
+
+  let state = app_handle.state::<DbState>();
+  let Ok(conn_guard) = state.0.lock() else {
+    log::warn!("[token_usage] Failed to acquire DB lock");
+    return;
+  };
+  let Some(conn) = conn_guard.as_ref() else {
+    return;
+  };
+
+  if let Err(e) = insert_conversation_usage(
+    conn,
+    &conversation_id,
+    provider,
+    model,
+    prompt_tokens,
+    completion_tokens,
+  ) {
+    log::warn!("[token_usage] Failed to record conversation usage: {}", e);
+  }
+}
+
+fn insert_conversation_usage(
+  conn: &Connection,
+  conversation_id: &str,
+  provider: &str,
+  model: &str,
+  prompt_tokens: u64,
+  completion_tokens: u64,
+) -> Result<(), String> {
+  conn
+    .execute(
+      "INSERT INTO conversation_usage (conversation_id, model, provider, prompt_tokens, completion_tokens, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+      params![
+        conversation_id,
+        model,
+        provider,
+        prompt_tokens,
+        completion_tokens,
+        Utc::now().to_rfc3339(),
+      ],
+    )
+    .map_err(|e| format!("Failed to insert conversation usage: {}", e))?;
+  Ok(())
+}
+
+/// Get the tokens a conversation used per model, with estimated cloud cost
+#[tauri::command]
+pub async fn get_conversation_usage(
+  app_handle: AppHandle,
+  conversation_id: String,
+) -> Result<ConversationUsage, String> {
+  let prices = crate::settings::service::load_user_settings(app_handle.clone())
+    .await
+    .map_err(|e| format!("Failed to load user settings: {}", e))?
+    .model_prices;
+
+  let state = app_handle.state::<DbState>();
+  let db_guard = state.0.lock().unwrap();
+  let conn = db_guard
+    .as_ref()
+    .ok_or("Database connection not available")?;
+
+  query_conversation_usage(conn, &conversation_id, &prices)
+}
+
+fn query_conversation_usage(
+  conn: &Connection,
+  conversation_id: &str,
+  prices: &[ModelPrice],
+) -> Result<ConversationUsage, String> {
+  let mut stmt = conn
+    .prepare(
+      "SELECT model, provider, SUM(prompt_tokens), SUM(completion_tokens)
+         FROM conversation_usage
+         WHERE conversation_id = ?1
+         GROUP BY model, provider
+         ORDER BY model ASC",
+    )
+    .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+  let models = stmt
+    .query_map(params![conversation_id], |row| {
+      Ok(ConversationModelUsage {
+        model: row.get(0)?,
+        provider: row.get(1)?,
+        prompt_tokens: row.get(2)?,
+        completion_tokens: row.get(3)?,
+        estimated_cost: 0.0,
+      })
+    })
+    .map_err(|e| format!("Failed to query conversation usage: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Failed to read row: {}", e))?
+    .into_iter()
+    .map(|mut usage| {
+      if let Some(price) = prices.iter().find(|p| p.model == usage.model) {
+        usage.estimated_cost = (usage.prompt_tokens as f64 * price.prompt_per_million
+          + usage.completion_tokens as f64 * price.completion_per_million)
+          / 1_000_000.0;
+      }
+      usage
+    })
+    .collect::<Vec<_>>();
+
+  Ok(ConversationUsage {
+    conversation_id: conversation_id.to_string(),
+    prompt_tokens: models.iter().map(|m| m.prompt_tokens).sum(),
+    completion_tokens: models.iter().map(|m| m.completion_tokens).sum(),
+    estimated_cost: models.iter().map(|m| m.estimated_cost).sum(),
+    models,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::types::default_model_prices;
+
+  #[test]
+  fn test_conversation_usage_is_totaled_and_priced_per_model() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::core::prepare_connection(&mut conn, false).unwrap();
+    conn
+      .execute(
+        "INSERT INTO conversations (id, name, created_at, updated_at) VALUES ('conv-1', 'A', '', '')",
+        [],
+      )
+      .unwrap();
+
+    insert_conversation_usage(&conn, "conv-1", "local", "local", 500, 100).unwrap();
+    insert_conversation_usage(&conn, "conv-1", "cloudflare", "pro", 1_000_000, 0).unwrap();
+    insert_conversation_usage(&conn, "conv-1", "cloudflare", "pro", 0, 500_000).unwrap();
+
+    let usage = query_conversation_usage(&conn, "conv-1", &default_model_prices()).unwrap();
+    assert_eq!(usage.prompt_tokens, 1_000_500);
+    assert_eq!(usage.completion_tokens, 500_100);
+    assert_eq!(usage.models.len(), 2);
+    assert_eq!(usage.models[0].model, "local");
+    assert_eq!(usage.models[0].estimated_cost, 0.0);
+    assert_eq!(usage.models[1].estimated_cost, 8.0);
+    assert_eq!(usage.estimated_cost, 8.0);
+
+    assert!(query_conversation_usage(&conn, "other", &[]).unwrap().models.is_empty());
+  }
+}
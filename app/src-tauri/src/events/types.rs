@@ -24,6 +24,9 @@ pub const DOWNLOAD_PROGRESS: &str = "download_progress";
 pub struct DownloadProgressEvent {
   pub id: u64,
   pub total_progress: u64,
+  /// Size of the file, when the server reports it
+  pub total: Option<u64>,
+  pub bytes_per_second: u64,
 }
 
 pub const DOWNLOAD_FINISHED: &str = "download_finished";
@@ -151,4 +154,41 @@ pub const TOKEN_USAGE_CHANGED: &str = "token_usage_changed";
 #[ts(export, export_to = "events.ts")]
 pub struct TokenUsageChangedEvent {
   pub timestamp: String,
-}
\ No newline at end of file
+}
+
+pub const MESSAGES_DELETED: &str = "messages_deleted";
+#[derive(Serialize, Deserialize, Clone, Debug, TS)]
+#[ts(export, export_to = "events.ts")]
+pub struct MessagesDeletedEvent {
+  pub conversation_id: String,
+  pub message_ids: Vec<String>,
+  pub timestamp: String,
+}
+
+pub const MODEL_SWITCHED: &str = "model_switched";
+#[derive(Serialize, Deserialize, Clone, Debug, TS)]
+#[ts(export, export_to = "events.ts")]
+pub struct ModelSwitchedEvent {
+  /// None when back on the bundled model
+  pub model_path: Option<String>,
+  pub timestamp: String,
+}
+
+pub const SERVER_RESTARTED: &str = "server_restarted";
+#[derive(Serialize, Deserialize, Clone, Debug, TS)]
+#[ts(export, export_to = "events.ts")]
+pub struct ServerRestartedEvent {
+  pub restart_count: u32,
+  pub timestamp: String,
+}
+
+pub const PROVIDER_FALLBACK: &str = "provider_fallback";
+#[derive(Serialize, Deserialize, Clone, Debug, TS)]
+#[ts(export, export_to = "events.ts")]
+pub struct ProviderFallbackEvent {
+  pub conv_id: Option<String>,
+  /// Models that failed, in the order they were tried
+  pub failed_models: Vec<String>,
+  pub answered_model: String,
+  pub timestamp: String,
+}
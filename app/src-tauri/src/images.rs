@@ -1,9 +1,154 @@
 use crate::screen_selection::SelectionBounds;
+use base64::{engine::general_purpose, Engine as _};
 use image::imageops::{crop, FilterType};
 use screenshots::Screen;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
+use ts_rs::TS;
+
+/// A display that can be captured
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "images.ts")]
+pub struct MonitorInfo {
+  pub index: usize,
+  pub x: i32,
+  pub y: i32,
+  pub width: u32,
+  pub height: u32,
+  pub scale_factor: f32,
+  pub is_primary: bool,
+}
+
+/// Rectangle to capture, relative to the monitor's top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "images.ts")]
+pub struct ScreenRegion {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// PNG screenshot along with the monitor and region it was taken from
+#[derive(Debug, Clone)]
+pub struct ScreenCapture {
+  pub monitor_index: usize,
+  pub region: ScreenRegion,
+  pub png: Vec<u8>,
+}
+
+/// Screenshot returned to the frontend, with the image as base64 PNG
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "images.ts")]
+pub struct ScreenshotResponse {
+  pub monitor_index: usize,
+  pub region: ScreenRegion,
+  pub image_base64: String,
+}
+
+/// List the monitors available to capture
+#[tauri::command]
+pub fn list_monitors() -> Result<Vec<MonitorInfo>, String> {
+  let screens = Screen::all().map_err(|e| format!("Failed to list monitors: {}", e))?;
+  Ok(
+    screens
+      .iter()
+      .enumerate()
+      .map(|(index, screen)| {
+        let info = screen.display_info;
+        MonitorInfo {
+          index,
+          x: info.x,
+          y: info.y,
+          width: info.width,
+          height: info.height,
+          scale_factor: info.scale_factor,
+          is_primary: info.is_primary,
+        }
+      })
+      .collect(),
+  )
+}
+
+/// Capture a monitor, or a region of it. Defaults to the whole primary monitor.
+pub fn capture_screen(
+  monitor_index: Option<usize>,
+  region: Option<ScreenRegion>,
+) -> Result<ScreenCapture, String> {
+  let screens = Screen::all().map_err(|e| format!("Failed to list monitors: {}", e))?;
+  let monitor_index = match monitor_index {
+    Some(index) => index,
+    None => screens
+      .iter()
+      .position(|s| s.display_info.is_primary)
+      .unwrap_or(0),
+  };
+  let screen = screens.get(monitor_index).ok_or_else(|| {
+    format!(
+      "Monitor {} does not exist, {} monitor(s) available",
+      monitor_index,
+      screens.len()
+    )
+  })?;
+
+  let info = screen.display_info;
+  let image = match region {
+    Some(region) => {
+      validate_region(&region, info.width, info.height)?;
+      screen.capture_area(region.x as i32, region.y as i32, region.width, region.height)
+    }
+    None => screen.capture(),
+  }
+  .map_err(|e| format!("Failed to capture monitor {}: {}", monitor_index, e))?;
+
+  let mut buffer = std::io::Cursor::new(Vec::new());
+  image
+    .write_to(&mut buffer, screenshots::image::ImageFormat::Png)
+    .map_err(|e| format!("Failed to encode screenshot: {}", e))?;
+
+  Ok(ScreenCapture {
+    monitor_index,
+    region: region.unwrap_or(ScreenRegion {
+      x: 0,
+      y: 0,
+      width: info.width,
+      height: info.height,
+    }),
+    png: buffer.into_inner(),
+  })
+}
+
+/// Take a screenshot of a monitor or a region of it
+#[tauri::command]
+pub fn take_screenshot_region(
+  monitor_index: Option<usize>,
+  region: Option<ScreenRegion>,
+) -> Result<ScreenshotResponse, String> {
+  let capture = capture_screen(monitor_index, region)?;
+  Ok(ScreenshotResponse {
+    monitor_index: capture.monitor_index,
+    region: capture.region,
+    image_base64: general_purpose::STANDARD.encode(&capture.png),
+  })
+}
+
+/// Reject empty regions and regions that extend past the monitor
+fn validate_region(region: &ScreenRegion, monitor_width: u32, monitor_height: u32) -> Result<(), String> {
+  if region.width == 0 || region.height == 0 {
+    return Err("Screenshot region must have a non-zero width and height".to_string());
+  }
+  let right = region.x as u64 + region.width as u64;
+  let bottom = region.y as u64 + region.height as u64;
+  if right > monitor_width as u64 || bottom > monitor_height as u64 {
+    return Err(format!(
+      "Screenshot region {}x{} at ({}, {}) is outside the {}x{} monitor",
+      region.width, region.height, region.x, region.y, monitor_width, monitor_height
+    ));
+  }
+  Ok(())
+}
 
 pub fn save_screenshot(app_handle: &AppHandle, filename: String) -> String {
   let screens = Screen::all().unwrap();
@@ -23,14 +168,9 @@ pub fn save_screenshot(app_handle: &AppHandle, filename: String) -> String {
   screenshot_path.to_str().unwrap().to_string()
 }
 
-// Returns png data for screenshot
+// Returns png data for a screenshot of the primary monitor
 pub fn take_screenshot() -> Vec<u8> {
-  let screens = Screen::all().unwrap();
-  let screen = &screens[0]; // Assuming single screen for simplicity
-  let image = screen.capture().unwrap();
-  let mut buffer = std::io::Cursor::new(Vec::new());
-  image.write_to(&mut buffer, screenshots::image::ImageFormat::Png).unwrap();
-  buffer.into_inner()
+  capture_screen(None, None).unwrap().png
 }
 
 pub fn crop_image_selection(path: PathBuf, selection: SelectionBounds) {
@@ -55,3 +195,19 @@ pub fn resize_image(path: PathBuf) {
     .save(&path)
     .expect("Failed to save resized image");
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_region_must_fit_within_the_monitor() {
+    let region = |x, y, width, height| ScreenRegion { x, y, width, height };
+    assert!(validate_region(&region(0, 0, 1920, 1080), 1920, 1080).is_ok());
+    assert!(validate_region(&region(100, 200, 300, 400), 1920, 1080).is_ok());
+    assert!(validate_region(&region(1800, 0, 200, 100), 1920, 1080).is_err());
+    assert!(validate_region(&region(0, 1000, 100, 100), 1920, 1080).is_err());
+    assert!(validate_region(&region(0, 0, 0, 100), 1920, 1080).is_err());
+    assert!(validate_region(&region(u32::MAX, 0, 10, 10), 1920, 1080).is_err());
+  }
+}
@@ -9,6 +9,7 @@ pub mod models;
 pub mod settings;
 pub mod screen_selection;
 pub mod setup;
+pub mod storage;
 pub mod tray;
 pub mod windows;
 use db::core::DbState;
@@ -76,10 +77,17 @@ pub fn run() {
       match db::core::initialize_database(&app_handle) {
         Ok(conn) => {
           log::info!("[setup] Database initialized successfully.");
+          if let Err(e) = db::conversations::cleanup_orphaned_attachments(&app_handle, &conn) {
+            log::warn!("[setup] Failed to clean up orphaned attachments: {}", e);
+          }
           // Store the connection in the managed state using the app_handle
           let state = app_handle.state::<DbState>();
           *state.0.lock().unwrap() = Some(conn);
         }
+        Err(e @ db::core::DbError::SchemaTooNew(_)) => {
+          log::error!("[setup] {}. Update Ambient to open it.", e);
+          panic!("Database initialization failed: {}. Update Ambient to open it.", e);
+        }
         Err(e) => {
           log::error!("[setup] Failed to initialize database: {}", e);
           panic!("Database initialization failed: {}", e);
@@ -98,6 +106,9 @@ pub fn run() {
         }
       });
 
+      // Restart the llama.cpp server if it crashes
+      models::llm::watchdog::spawn_watchdog(app.handle().clone());
+
       // Create the system tray
       if let Err(e) = tray::create_tray(&app.handle()) {
         log::error!("[setup] Failed to create system tray: {}", e);
@@ -149,26 +160,54 @@ pub fn run() {
       screen_selection::process_screen_selection,
       screen_selection::cancel_screen_selection,
       screen_selection::get_screen_dimensions,
+      images::list_monitors,
+      images::take_screenshot_region,
       db::core::execute_sql,
       db::core::reset_database,
+      db::core::get_schema_version,
       db::conversations::create_conversation,
       db::conversations::get_messages,
       db::conversations::get_message,
       db::conversations::get_conversation,
       db::conversations::list_conversations,
+      db::conversations::list_conversations_paged,
+      db::conversations::count_conversations,
+      db::conversations::search_messages,
       db::conversations::delete_conversation,
       db::conversations::update_conversation_name,
+      db::conversations::edit_message,
+      db::conversations::fork_conversation,
+      db::conversations::insert_context_boundary,
+      db::conversations::set_conversation_pinned,
+      db::conversations::set_conversation_archived,
+      db::conversations::export_conversation,
+      db::conversations::get_conversation_streaming,
+      db::conversations::set_conversation_streaming,
       db::memory::get_memory_entries_with_message,
+      db::memory::get_memories,
+      db::memory::search_memories,
       db::memory::delete_memory_entry,
       db::memory::delete_all_memories,
       db::token_usage::get_token_usage_consumption,
+      db::token_usage::get_conversation_usage,
       db::token_usage::get_token_usage,
+      db::llm_debug::get_last_llm_exchange,
       setup::setup,
       setup::get_setup_download_info,
       setup::check_setup_complete,
+      setup::list_models,
+      setup::verify_model,
+      storage::get_storage_breakdown,
       models::llm::server::spawn_llama_server,
+      models::llm::server::restart_llama_server,
+      models::llm::server::switch_model,
+      models::llm::server::get_server_status,
       models::llm::handlers::handle_hud_chat,
+      models::llm::handlers::regenerate_from,
+      models::llm::handlers::resume_conversation,
+      models::llm::compare::compare_models,
       models::embedding::embedding::generate_embedding,
+      models::embedding::embedding::embed_texts,
       models::ocr::ocr::process_image,
       models::computer_use::commands::start_computer_use,
       models::computer_use::commands::stop_computer_use,
@@ -183,6 +222,8 @@ pub fn run() {
       auth::commands::get_user,
       auth::commands::get_access_token_command,
       auth::commands::emit_auth_changed,
+      auth::commands::list_stored_credentials,
+      auth::commands::revoke_credential,
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
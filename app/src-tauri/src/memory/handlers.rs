@@ -3,7 +3,7 @@ use crate::db::memory::insert_memory_entry;
 use crate::events::{emitter::emit, types::*};
 use crate::memory::types::MemoryEntry;
 use crate::models::embedding::embedding::generate_embedding;
-use crate::models::llm::{client::generate, prompts::get_prompt, schemas::get_schema, types::LlmRequest};
+use crate::models::llm::{client::generate, prompts::get_prompt, schemas::get_schema, types::{LlmRequest, ProviderPolicy}};
 use chrono;
 use tauri::{AppHandle, Manager};
 
@@ -43,7 +43,7 @@ pub async fn handle_extract_interactive_memory(
     .with_use_thinking(Some(false))
     .with_stream(Some(false));
 
-  let extracted_memory = match generate(app_handle.clone(), request, Some(true)).await {
+  let extracted_memory = match generate(app_handle.clone(), request, ProviderPolicy::ForceLocal).await {
     Ok(generated) => {
       generated
     }
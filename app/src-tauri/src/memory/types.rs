@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use ts_rs::TS;
 
 #[derive(Serialize, Deserialize, Clone, Debug, TS)]
@@ -12,3 +13,33 @@ pub struct MemoryEntry {
   pub timestamp: String,
   pub similarity: Option<f64>,
 }
+
+/// How `search_memories` trades similarity off against recency and memory type
+#[derive(Serialize, Deserialize, Clone, Debug, TS)]
+#[serde(default)]
+#[ts(export, export_to = "memory.ts")]
+pub struct MemoryRankWeights {
+  /// Weight of the recency term, which halves every `half_life_days`
+  pub recency: f64,
+  pub half_life_days: f64,
+  /// Added to the score of memories of each type, e.g. `{ "semantic": 0.1 }`
+  pub type_boosts: HashMap<String, f64>,
+}
+
+impl Default for MemoryRankWeights {
+  fn default() -> Self {
+    Self {
+      recency: 0.2,
+      half_life_days: 30.0,
+      type_boosts: HashMap::new(),
+    }
+  }
+}
+
+/// A memory search result with the score it was ranked by
+#[derive(Serialize, Deserialize, Clone, Debug, TS)]
+#[ts(export, export_to = "memory.ts")]
+pub struct RankedMemory {
+  pub memory: MemoryEntry,
+  pub score: f64,
+}
@@ -13,8 +13,8 @@ use crate::db::conversations::add_message;
 use crate::windows::{open_main_window, close_main_window, open_computer_use_window, close_computer_use_window};
 use crate::db::computer_use::{get_computer_use_session, save_computer_use_session};
 use crate::auth::commands::get_access_token_command;
-use crate::db::token_usage::add_token_usage;
-use crate::constants::CLOUDFLARE_COMPLETIONS_WORKER_URL;
+use crate::db::token_usage::{add_token_usage, record_conversation_usage};
+use crate::constants::{CLOUDFLARE_COMPLETIONS_WORKER_URL, COMPUTER_USE_REPEAT_LIMIT};
 use chrono;
 
 fn transform_function_call(function_name: String, args: Vec<String>) -> (String, String) {
@@ -74,6 +74,134 @@ fn transform_function_call(function_name: String, args: Vec<String>) -> (String,
     (message_content, toast_content)
 }
 
+/// Describe an action before it runs, for the user to confirm
+fn describe_planned_action(
+    name: &str,
+    args: &serde_json::Value,
+    to_screen: impl Fn(i32, i32) -> (i32, i32),
+) -> String {
+    let point = |x: &str, y: &str| {
+        let (x, y) = to_screen(
+            args[x].as_i64().unwrap_or(0) as i32,
+            args[y].as_i64().unwrap_or(0) as i32,
+        );
+        format!("({}, {})", x, y)
+    };
+    let text = |key: &str| args[key].as_str().unwrap_or_default().to_string();
+    match name {
+        "open_web_browser" | "search" => "Open the web browser".to_string(),
+        "wait_5_seconds" => "Wait for 5 seconds".to_string(),
+        "go_back" => "Go back".to_string(),
+        "go_forward" => "Go forward".to_string(),
+        "navigate" => format!("Navigate to {}", text("url")),
+        "click_at" => format!("Click at {}", point("x", "y")),
+        "hover_at" => format!("Move the mouse to {}", point("x", "y")),
+        "type_text_at" => format!("Type '{}' at {}", text("text"), point("x", "y")),
+        "key_combination" => format!("Press keys '{}'", text("keys")),
+        "scroll_document" => format!("Scroll {}", text("direction")),
+        "scroll_at" => format!("Scroll {} at {}", text("direction"), point("x", "y")),
+        "drag_and_drop" => format!(
+            "Drag from {} to {}",
+            point("x", "y"),
+            point("destination_x", "destination_y")
+        ),
+        other => format!("Run '{}'", other),
+    }
+}
+
+const REPEAT_NUDGE: &str = "You've repeated this action several times without making progress. Try a different approach or answer the user directly.";
+const REPEAT_STOP_RESPONSE: &str = "I kept repeating the same action without making progress, so I stopped. Please try rephrasing your request.";
+const SAFETY_DECLINED_RESPONSE: &str = "You declined an action that needed a safety confirmation, so I stopped.";
+const ACTION_DECLINED_RESPONSE: &str = "You declined the next action, so I stopped.";
+
+#[derive(Debug, PartialEq)]
+enum LoopAction {
+    Continue,
+    Nudge,
+    Stop,
+}
+
+/// Tracks consecutive identical function calls on an unchanged screen so a stuck model
+/// can be nudged, then stopped. Repeating an action that still changes the screen, like
+/// scrolling through a long page, is progress and doesn't count.
+#[derive(Default)]
+struct LoopGuard {
+    last_signature: Option<String>,
+    repeats: usize,
+    nudged: bool,
+}
+
+impl LoopGuard {
+    /// `screen` identifies the screenshot the model saw when it made the calls
+    fn observe(&mut self, function_calls: &[serde_json::Value], screen: Option<u64>) -> LoopAction {
+        let calls = function_calls
+            .iter()
+            .map(|call| {
+                let name = call.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+                let args = call.get("args").cloned().unwrap_or_default();
+                format!("{}:{}", name, args)
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        let signature = format!("{}@{:?}", calls, screen);
+
+        if self.last_signature.as_deref() == Some(signature.as_str()) {
+            self.repeats += 1;
+        } else {
+            self.last_signature = Some(signature);
+            self.repeats = 1;
+            self.nudged = false;
+        }
+
+        if self.repeats < COMPUTER_USE_REPEAT_LIMIT {
+            LoopAction::Continue
+        } else if !self.nudged {
+            self.nudged = true;
+            LoopAction::Nudge
+        } else {
+            LoopAction::Stop
+        }
+    }
+}
+
+/// Answer each repeated call with an error instead of executing it again, so every
+/// function call in the history keeps a matching response
+fn repeat_error_parts(function_calls: &[serde_json::Value], error: &str) -> Vec<serde_json::Value> {
+    function_calls
+        .iter()
+        .map(|call| {
+            let name = call.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+            json!({
+                "functionResponse": {
+                    "name": name,
+                    "response": {
+                        "error": error
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Results of the actions that already ran in a turn, followed by an error for each call
+/// that won't run, so a turn cut short still answers every function call
+fn declined_turn_parts(
+    mut parts: Vec<serde_json::Value>,
+    remaining_calls: &[serde_json::Value],
+    error: &str,
+) -> Vec<serde_json::Value> {
+    parts.extend(repeat_error_parts(remaining_calls, error));
+    parts
+}
+
+/// Fingerprint of a screenshot, to tell whether an action changed the screen
+fn screen_fingerprint(screenshot: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    screenshot.hash(&mut hasher);
+    hasher.finish()
+}
+
 pub struct ComputerUseEngine {
     app_handle: AppHandle,
     prompt: String,
@@ -83,6 +211,11 @@ pub struct ComputerUseEngine {
     final_response: String,
     contents: Vec<serde_json::Value>,
     should_stop: Arc<AtomicBool>,
+    loop_guard: LoopGuard,
+    /// Fingerprint of the latest screenshot sent to the model
+    last_screen: Option<u64>,
+    /// When false, every action waits for the user to confirm it
+    autonomous: bool,
 }
 
 impl ComputerUseEngine {
@@ -134,6 +267,12 @@ impl ComputerUseEngine {
             }]
         });
         contents.push(initial_content);
+
+        let autonomous = crate::settings::service::load_user_settings(app_handle.clone())
+            .await
+            .map(|settings| settings.computer_use_autonomous)
+            .unwrap_or(false);
+
         Self {
             app_handle: app_handle.clone(),
             prompt,
@@ -143,6 +282,9 @@ impl ComputerUseEngine {
             contents,
             conversation_id,
             should_stop,
+            loop_guard: LoopGuard::default(),
+            last_screen: None,
+            autonomous,
         }
     }
 
@@ -216,6 +358,15 @@ impl ComputerUseEngine {
             prompt_tokens,
             completion_tokens,
         ).await?;
+        record_conversation_usage(
+            &self.app_handle,
+            Some(self.conversation_id.clone()),
+            "cloudflare",
+            "computer-use",
+            prompt_tokens,
+            completion_tokens,
+        )
+        .await;
 
         Ok(json_response)
     }
@@ -359,11 +510,16 @@ impl ComputerUseEngine {
 
     async fn get_safety_confirmation(&self, safety: &serde_json::Value) -> Result<bool, String> {
         log::info!("[computer_use] Safety confirmation required");
+        let reason = safety.get("explanation").and_then(|e| e.as_str()).unwrap_or("No explanation provided");
+        self.get_user_confirmation(reason.to_string()).await
+    }
+
+    async fn get_user_confirmation(&self, reason: String) -> Result<bool, String> {
         // Ensure the toast window is open
         let _ = open_computer_use_window(self.app_handle.clone()).await;
 
         let safety_confirmation_event = SafetyConfirmationEvent {
-            reason: safety.get("explanation").and_then(|e| e.as_str()).unwrap_or("No explanation provided").to_string(),
+            reason,
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
         let _ = emit(GET_SAFETY_CONFIRMATION, safety_confirmation_event);
@@ -460,6 +616,22 @@ impl ComputerUseEngine {
         Ok(())
     }
 
+    /// Stop after the user declined a call, answering it and the calls after it
+    async fn stop_declined(
+        &mut self,
+        parts: Vec<serde_json::Value>,
+        remaining_calls: &[serde_json::Value],
+        response: &str,
+    ) -> Result<bool, String> {
+        self.contents.push(json!({
+            "role": "user",
+            "parts": declined_turn_parts(parts, remaining_calls, response)
+        }));
+        self.final_response = response.to_string();
+        let _ = self.save_contents_to_db().await;
+        Ok(true)
+    }
+
     async fn save_contents_to_db(&mut self) -> Result<(), String> {
         save_computer_use_session(
             self.app_handle.clone(),
@@ -518,6 +690,34 @@ impl ComputerUseEngine {
 
             return Ok(true);
         }
+
+        // Check for the model repeating the same action
+        match self.loop_guard.observe(&function_calls, self.last_screen) {
+            LoopAction::Continue => {}
+            LoopAction::Nudge => {
+                log::warn!("[computer_use] Repeated function calls detected, nudging model");
+                self.contents.push(json!({
+                    "role": "user",
+                    "parts": repeat_error_parts(&function_calls, REPEAT_NUDGE)
+                }));
+                let _ = self.save_contents_to_db().await;
+                return Ok(false);
+            }
+            LoopAction::Stop => {
+                log::warn!("[computer_use] Model kept repeating function calls after nudge, stopping");
+                self.contents.push(json!({
+                    "role": "user",
+                    "parts": repeat_error_parts(&function_calls, REPEAT_STOP_RESPONSE)
+                }));
+                self.final_response = if reasoning.is_empty() {
+                    REPEAT_STOP_RESPONSE.to_string()
+                } else {
+                    reasoning
+                };
+                let _ = self.save_contents_to_db().await;
+                return Ok(true);
+            }
+        }
         
         // Emit reasoning and save to db
         let _ = self.save_and_emit_reasoning_message(reasoning.clone()).await;
@@ -526,7 +726,7 @@ impl ComputerUseEngine {
         let mut function_names = Vec::new();
         let mut args = Vec::new();
         let mut parts = Vec::new();
-        for function_call in function_calls {
+        for (i, function_call) in function_calls.iter().enumerate() {
             let name = function_call.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
             log::info!("[computer_use] Handling function call: {}", name);
 
@@ -538,12 +738,29 @@ impl ComputerUseEngine {
                     let user_confirmed = self.get_safety_confirmation(safety).await?;
                     if !user_confirmed {
                         log::warn!("[computer_use] Safety confirmation denied by user, stopping execution");
-                        let _ = self.save_contents_to_db().await;
-                        return Ok(true);
+                        return self
+                            .stop_declined(parts, &function_calls[i..], SAFETY_DECLINED_RESPONSE)
+                            .await;
                     }
                 }
             }
-            let action_result = self.handle_action(&function_call).await;
+
+            // Outside autonomous mode, the user approves each action before it runs
+            if !safety_required && !self.autonomous {
+                let planned = describe_planned_action(
+                    name,
+                    function_call.get("args").unwrap_or(&serde_json::Value::Null),
+                    |x, y| self.denormalize_coordinates(x, y),
+                );
+                log::info!("[computer_use] Asking the user to confirm: {}", planned);
+                if !self.get_user_confirmation(planned).await? {
+                    log::warn!("[computer_use] Action declined by user, stopping execution");
+                    return self
+                        .stop_declined(parts, &function_calls[i..], ACTION_DECLINED_RESPONSE)
+                        .await;
+                }
+            }
+            let action_result = self.handle_action(function_call).await;
             if action_result.is_err() {
                 log::error!("[computer_use] Error handling action: {}", action_result.err().unwrap());
                 return Ok(false);
@@ -561,6 +778,7 @@ impl ComputerUseEngine {
             // Wait 1 second for UI to update
             tokio::time::sleep(std::time::Duration::from_secs(1)).await;
             let screenshot_vec = take_screenshot();
+            self.last_screen = Some(screen_fingerprint(&screenshot_vec));
             let screenshot_data = general_purpose::STANDARD.encode(&screenshot_vec);
 
             // Create part with function call result
@@ -685,4 +903,81 @@ impl ComputerUseEngine {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declined_turn_keeps_results_and_answers_remaining_calls() {
+        let calls = [
+            json!({ "name": "click_at", "args": { "x": 10, "y": 20 } }),
+            json!({ "name": "type_text_at", "args": { "text": "hi" } }),
+            json!({ "name": "key_combination", "args": { "keys": "enter" } }),
+        ];
+        let ran = vec![json!({ "functionResponse": { "name": "click_at", "response": {} } })];
+
+        let parts = declined_turn_parts(ran, &calls[1..], ACTION_DECLINED_RESPONSE);
+        let names: Vec<_> = parts.iter().map(|p| p["functionResponse"]["name"].clone()).collect();
+        assert_eq!(names, ["click_at", "type_text_at", "key_combination"]);
+        assert!(parts[0]["functionResponse"]["response"].get("error").is_none());
+        assert_eq!(parts[2]["functionResponse"]["response"]["error"], ACTION_DECLINED_RESPONSE);
+    }
+
+    #[test]
+    fn test_repeated_call_triggers_nudge_then_stop() {
+        let click = vec![json!({ "name": "click_at", "args": { "x": 10, "y": 20 } })];
+        let screen = Some(screen_fingerprint(b"same screen"));
+        let mut guard = LoopGuard::default();
+
+        assert_eq!(guard.observe(&click, screen), LoopAction::Continue);
+        assert_eq!(guard.observe(&click, screen), LoopAction::Continue);
+        assert_eq!(guard.observe(&click, screen), LoopAction::Nudge);
+        assert_eq!(guard.observe(&click, screen), LoopAction::Stop);
+
+        let parts = repeat_error_parts(&click, REPEAT_NUDGE);
+        assert_eq!(parts[0]["functionResponse"]["name"], "click_at");
+        assert_eq!(parts[0]["functionResponse"]["response"]["error"], REPEAT_NUDGE);
+
+        // A different call resets the count
+        let other = vec![json!({ "name": "click_at", "args": { "x": 30, "y": 20 } })];
+        assert_eq!(guard.observe(&other, screen), LoopAction::Continue);
+        assert_eq!(guard.observe(&click, screen), LoopAction::Continue);
+    }
+
+    #[test]
+    fn test_repeated_action_that_changes_the_screen_is_not_a_loop() {
+        let scroll = vec![json!({ "name": "scroll_document", "args": { "direction": "down" } })];
+        let mut guard = LoopGuard::default();
+
+        for page in 0..10u8 {
+            let screen = Some(screen_fingerprint(&[page]));
+            assert_eq!(guard.observe(&scroll, screen), LoopAction::Continue);
+        }
+
+        // Scrolling once the page stops moving is stuck
+        let bottom = Some(screen_fingerprint(b"bottom of page"));
+        assert_eq!(guard.observe(&scroll, bottom), LoopAction::Continue);
+        assert_eq!(guard.observe(&scroll, bottom), LoopAction::Continue);
+        assert_eq!(guard.observe(&scroll, bottom), LoopAction::Nudge);
+    }
+
+    #[test]
+    fn test_planned_actions_are_described_in_screen_coordinates() {
+        let double = |x: i32, y: i32| (x * 2, y * 2);
+        assert_eq!(
+            describe_planned_action("click_at", &json!({ "x": 10, "y": 20 }), double),
+            "Click at (20, 40)"
+        );
+        assert_eq!(
+            describe_planned_action("type_text_at", &json!({ "x": 1, "y": 2, "text": "hello" }), double),
+            "Type 'hello' at (2, 4)"
+        );
+        assert_eq!(
+            describe_planned_action("navigate", &json!({ "url": "https://example.com" }), double),
+            "Navigate to https://example.com"
+        );
+        assert_eq!(describe_planned_action("open_file", &json!({}), double), "Run 'open_file'");
+    }
+}
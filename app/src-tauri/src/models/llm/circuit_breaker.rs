@@ -0,0 +1,96 @@
+use crate::constants::{CLOUD_BREAKER_COOLDOWN, CLOUD_BREAKER_FAILURE_THRESHOLD};
+use crate::settings::types::ModelSelection;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Shared by every caller of the cloud models
+pub static CLOUD_BREAKER: Lazy<CircuitBreaker> =
+  Lazy::new(|| CircuitBreaker::new(CLOUD_BREAKER_FAILURE_THRESHOLD, CLOUD_BREAKER_COOLDOWN));
+
+#[derive(Debug, Default)]
+struct ModelCircuit {
+  consecutive_failures: u8,
+  opened_at: Option<Instant>,
+}
+
+/// Stops calling a model for a while after it fails several times in a row, so an
+/// outage fails fast instead of waiting out a timeout on every request
+pub struct CircuitBreaker {
+  threshold: u8,
+  cooldown: Duration,
+  circuits: Mutex<HashMap<&'static str, ModelCircuit>>,
+}
+
+impl CircuitBreaker {
+  pub fn new(threshold: u8, cooldown: Duration) -> Self {
+    Self {
+      threshold,
+      cooldown,
+      circuits: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Refuse the model while its circuit is open. Once the cooldown has passed a call
+  /// is let through to probe whether it has recovered.
+  pub fn check(&self, model: ModelSelection) -> Result<(), String> {
+    let circuits = self.circuits.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(opened_at) = circuits.get(model.as_str()).and_then(|c| c.opened_at) else {
+      return Ok(());
+    };
+    let elapsed = opened_at.elapsed();
+    if elapsed >= self.cooldown {
+      return Ok(());
+    }
+    Err(format!(
+      "{} is unavailable after repeated failures, try again in {}s",
+      model.as_str(),
+      (self.cooldown - elapsed).as_secs().max(1)
+    ))
+  }
+
+  pub fn record(&self, model: ModelSelection, succeeded: bool) {
+    let mut circuits = self.circuits.lock().unwrap_or_else(|e| e.into_inner());
+    if succeeded {
+      circuits.remove(model.as_str());
+      return;
+    }
+    let circuit = circuits.entry(model.as_str()).or_default();
+    circuit.consecutive_failures = circuit.consecutive_failures.saturating_add(1);
+    if circuit.consecutive_failures >= self.threshold {
+      if circuit.opened_at.is_none() {
+        log::warn!(
+          "[circuit_breaker] {} failed {} times in a row, pausing it for {:?}",
+          model.as_str(),
+          circuit.consecutive_failures,
+          self.cooldown
+        );
+      }
+      // A failed probe after the cooldown opens the circuit again
+      circuit.opened_at = Some(Instant::now());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_opens_after_threshold_and_closes_on_success() {
+    let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+    breaker.record(ModelSelection::Fast, false);
+    assert!(breaker.check(ModelSelection::Fast).is_ok());
+    breaker.record(ModelSelection::Fast, false);
+    assert!(breaker.check(ModelSelection::Fast).is_err());
+    // Other models keep their own circuit
+    assert!(breaker.check(ModelSelection::Pro).is_ok());
+
+    let probing = CircuitBreaker::new(1, Duration::ZERO);
+    probing.record(ModelSelection::Pro, false);
+    assert!(probing.check(ModelSelection::Pro).is_ok());
+    probing.record(ModelSelection::Pro, true);
+    assert!(probing.circuits.lock().unwrap().is_empty());
+  }
+}
@@ -1,41 +1,196 @@
 use super::providers::{
-  local::LocalProvider, cloudflare::CloudflareProvider
+  local::LocalProvider, cloudflare::CloudflareProvider, ollama::OllamaProvider
 };
-use super::types::{LlmRequest, ProviderPolicy, LlmProvider};
+use super::types::{LlmError, LlmRequest, ProviderPolicy, LlmProvider};
+use super::schemas::validate_response;
+use super::shutdown::begin_generation;
+use crate::events::{emitter::emit, types::{ProviderFallbackEvent, PROVIDER_FALLBACK}};
+use crate::settings::types::ModelSelection;
 use tauri::AppHandle;
 
-/// Unified generate function that routes to the selected provider.
+/// Unified generate function that routes to the selected provider. With the default
+/// policy, the user's fallback models are tried in order if it fails. Responses to a
+/// request with a JSON schema are checked against it, retrying once if they don't match.
 pub async fn generate(
   app_handle: AppHandle,
   request: LlmRequest,
-  force_local: Option<bool>,
-) -> Result<String, String> {
-  let policy = if force_local.unwrap_or(false) {
-    ProviderPolicy::ForceLocal
-  } else {
-    ProviderPolicy::Default
+  policy: ProviderPolicy,
+) -> Result<String, LlmError> {
+  // Checked before the fallback chain, since a provider that ignores sampling
+  // (Cloudflare) would otherwise "succeed" with a request the others rejected
+  request.sampling.validate().map_err(LlmError::InvalidRequest)?;
+
+  let Some(schema) = request.json_schema.clone() else {
+    return generate_unchecked(app_handle, request, policy).await;
+  };
+
+  let response = generate_unchecked(app_handle.clone(), request.clone(), policy).await?;
+  let Err(mismatch) = validate_response(&schema, &response) else {
+    return Ok(response);
   };
+  log::warn!("[llm] Response did not match the JSON schema, retrying: {}", mismatch);
+
+  let mut retry = request;
+  retry.system_prompt = Some(corrective_system_prompt(retry.system_prompt, &mismatch));
+  let response = generate_unchecked(app_handle, retry, policy).await?;
+  validate_response(&schema, &response)
+    .map_err(|e| format!("Response did not match the JSON schema: {}", e))?;
+  Ok(response)
+}
+
+async fn generate_unchecked(
+  app_handle: AppHandle,
+  mut request: LlmRequest,
+  policy: ProviderPolicy,
+) -> Result<String, LlmError> {
+  // Held until the response is returned, so quitting waits for it to be saved
+  let _generation = begin_generation()?;
 
   // Decide provider
-  let provider_is_local = match policy {
-    ProviderPolicy::ForceLocal => true,
-    ProviderPolicy::Default => {
+  let (model_selection, fallbacks) = match policy {
+    ProviderPolicy::ForceLocal => (ModelSelection::Local, Vec::new()),
+    ProviderPolicy::Default | ProviderPolicy::ForceCloud => {
       // Read settings to decide
       let settings = crate::settings::service::load_user_settings(app_handle.clone())
         .await
         .map_err(|e| format!("Failed to load user settings: {}", e))?;
-      matches!(
-        settings.model_selection,
-        crate::settings::types::ModelSelection::Local
-      )
+      let fallbacks = match policy {
+        ProviderPolicy::Default => settings.provider_fallbacks,
+        _ => Vec::new(),
+      };
+      (select_model(policy, settings.model_selection), fallbacks)
+    }
+  };
+
+  if policy == ProviderPolicy::ForceCloud {
+    if crate::auth::commands::get_access_token_command().await?.is_none() {
+      return Err("This task needs a cloud model. Please sign in to continue.".into());
+    }
+    // The cloud provider otherwise falls back to the user's selection, which may be local
+    request.model.get_or_insert(model_selection);
+  }
+
+  let chain = provider_chain(model_selection, &fallbacks);
+  let mut failed_models = Vec::new();
+  for (i, &model) in chain.iter().enumerate() {
+    let mut attempt = request.clone();
+    if i > 0 {
+      // The cloud provider otherwise uses the user's selection
+      attempt.model = Some(model);
+    }
+
+    match generate_with(app_handle.clone(), attempt, model).await {
+      Ok(response) => {
+        if !failed_models.is_empty() {
+          let event = ProviderFallbackEvent {
+            conv_id: request.conv_id.clone(),
+            failed_models,
+            answered_model: model.as_str().to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+          };
+          if let Err(e) = emit(PROVIDER_FALLBACK, event) {
+            log::warn!("[llm] Failed to emit provider fallback event: {}", e);
+          }
+        }
+        return Ok(response);
+      }
+      Err(e) => {
+        let Some(next) = chain.get(i + 1).filter(|_| e.is_retryable()) else {
+          return Err(e);
+        };
+        log::warn!(
+          "[llm] {} failed, falling back to {}: {}",
+          model.as_str(),
+          next.as_str(),
+          e
+        );
+        failed_models.push(model.as_str().to_string());
+      }
+    }
+  }
+
+  Err("No model available to generate with".into())
+}
+
+async fn generate_with(
+  app_handle: AppHandle,
+  request: LlmRequest,
+  model: ModelSelection,
+) -> Result<String, LlmError> {
+  let response = match model {
+    ModelSelection::Local => LocalProvider.generate(app_handle, request).await,
+    ModelSelection::Ollama => OllamaProvider.generate(app_handle, request).await,
+    ModelSelection::Fast | ModelSelection::Pro => {
+      CloudflareProvider.generate(app_handle, request).await
     }
   };
+  response.map(|response| response.text)
+}
+
+fn corrective_system_prompt(system_prompt: Option<String>, mismatch: &str) -> String {
+  format!(
+    "{}\n\nYour previous reply did not match the required JSON schema ({}). Reply with only JSON that matches the schema.",
+    system_prompt.unwrap_or_else(|| "You are a helpful assistant.".to_string()),
+    mismatch
+  )
+}
+
+/// The selected model followed by the fallbacks, each tried once
+fn provider_chain(selected: ModelSelection, fallbacks: &[ModelSelection]) -> Vec<ModelSelection> {
+  let mut chain = vec![selected];
+  for &model in fallbacks {
+    if !chain.contains(&model) {
+      chain.push(model);
+    }
+  }
+  chain
+}
+
+/// Apply the policy to the user's model selection. Forcing cloud keeps the user's
+/// cloud model if they picked one, and uses the fast model otherwise.
+fn select_model(policy: ProviderPolicy, user_selection: ModelSelection) -> ModelSelection {
+  match (policy, user_selection) {
+    (ProviderPolicy::ForceLocal, _) => ModelSelection::Local,
+    (ProviderPolicy::ForceCloud, ModelSelection::Pro) => ModelSelection::Pro,
+    (ProviderPolicy::ForceCloud, _) => ModelSelection::Fast,
+    (ProviderPolicy::Default, selection) => selection,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_force_cloud_never_selects_a_local_model() {
+    assert!(matches!(select_model(ProviderPolicy::ForceCloud, ModelSelection::Local), ModelSelection::Fast));
+    assert!(matches!(select_model(ProviderPolicy::ForceCloud, ModelSelection::Ollama), ModelSelection::Fast));
+    assert!(matches!(select_model(ProviderPolicy::ForceCloud, ModelSelection::Pro), ModelSelection::Pro));
+    assert!(matches!(select_model(ProviderPolicy::ForceLocal, ModelSelection::Pro), ModelSelection::Local));
+    assert!(matches!(select_model(ProviderPolicy::Default, ModelSelection::Ollama), ModelSelection::Ollama));
+  }
+
+  #[test]
+  fn test_fallback_chain_starts_with_selection_and_skips_repeats() {
+    assert_eq!(provider_chain(ModelSelection::Local, &[]), [ModelSelection::Local]);
+    assert_eq!(
+      provider_chain(
+        ModelSelection::Local,
+        &[ModelSelection::Ollama, ModelSelection::Local, ModelSelection::Fast, ModelSelection::Ollama]
+      ),
+      [ModelSelection::Local, ModelSelection::Ollama, ModelSelection::Fast]
+    );
+  }
+
+  #[test]
+  fn test_only_failures_before_output_fall_back() {
+    let unreachable = LlmError::from("Failed to reach Ollama: connection refused");
+    assert!(unreachable.is_retryable());
+    assert!(LlmError::from("Cloudflare error 429 Too Many Requests: slow down").is_retryable());
+    assert!(LlmError::ModelLoading.is_retryable());
 
-  if provider_is_local {
-    let provider = LocalProvider;
-    provider.generate(app_handle, request).await
-  } else {
-    let provider = CloudflareProvider;
-    provider.generate(app_handle, request).await
+    assert!(!LlmError::InvalidRequest("Invalid JSON schema provided".to_string()).is_retryable());
+    assert!(!LlmError::ShuttingDown.is_retryable());
+    assert!(!LlmError::Interrupted("Error reading stream: reset".to_string()).is_retryable());
   }
 }
@@ -0,0 +1,205 @@
+use crate::constants::COMPARE_MODEL_TIMEOUT;
+use crate::models::llm::providers::{
+  cloudflare::CloudflareProvider, local::LocalProvider, ollama::OllamaProvider,
+};
+use crate::models::llm::shutdown::begin_generation;
+use crate::models::llm::types::{LlmProvider, LlmRequest, LlmResponse};
+use crate::settings::types::ModelSelection;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use ts_rs::TS;
+
+/// Output and stats for a single model in a comparison
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "llm.ts")]
+pub struct ModelRunResult {
+  pub model: ModelSelection,
+  pub response: Option<String>,
+  pub error: Option<String>,
+  pub duration_ms: u64,
+  pub prompt_tokens: u64,
+  pub completion_tokens: u64,
+}
+
+/// Side-by-side results of running one prompt through two models
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "llm.ts")]
+pub struct ModelComparison {
+  pub prompt: String,
+  pub model_a: ModelRunResult,
+  pub model_b: ModelRunResult,
+}
+
+/// Run the same prompt through two models, one after the other, for A/B evaluation
+#[tauri::command]
+pub async fn compare_models(
+  app_handle: AppHandle,
+  prompt: String,
+  model_a: ModelSelection,
+  model_b: ModelSelection,
+) -> Result<ModelComparison, String> {
+  log::info!(
+    "[compare_models] Comparing {} and {}",
+    model_a.as_str(),
+    model_b.as_str()
+  );
+
+  Ok(
+    compare_with(prompt, model_a, model_b, COMPARE_MODEL_TIMEOUT, |prompt, model| {
+      run_model(app_handle.clone(), prompt, model)
+    })
+    .await,
+  )
+}
+
+/// Run the prompt through each model in turn with `run`
+async fn compare_with<F, Fut>(
+  prompt: String,
+  model_a: ModelSelection,
+  model_b: ModelSelection,
+  timeout: Duration,
+  run: F,
+) -> ModelComparison
+where
+  F: Fn(String, ModelSelection) -> Fut,
+  Fut: Future<Output = Result<LlmResponse, String>>,
+{
+  let result_a = run_timed(model_a, timeout, || run(prompt.clone(), model_a)).await;
+  let result_b = run_timed(model_b, timeout, || run(prompt.clone(), model_b)).await;
+
+  ModelComparison {
+    prompt,
+    model_a: result_a,
+    model_b: result_b,
+  }
+}
+
+/// Generate with a specific model. Cloud models go through the shared circuit breaker.
+async fn run_model(
+  app_handle: AppHandle,
+  prompt: String,
+  model: ModelSelection,
+) -> Result<LlmResponse, String> {
+  // Keeps shutdown and model switches from stopping the server mid-comparison
+  let _generation = begin_generation().map_err(|e| e.to_string())?;
+
+  let request = LlmRequest::new(prompt)
+    .with_use_thinking(Some(false))
+    .with_stream(Some(false))
+    .with_model(Some(model));
+  let response = match model {
+    ModelSelection::Local => LocalProvider.generate(app_handle, request).await?,
+    ModelSelection::Ollama => OllamaProvider.generate(app_handle, request).await?,
+    ModelSelection::Fast | ModelSelection::Pro => {
+      CloudflareProvider.generate(app_handle, request).await?
+    }
+  };
+  Ok(response)
+}
+
+/// Time a generation and fold its outcome into a `ModelRunResult`
+async fn run_timed<F, Fut>(model: ModelSelection, timeout: Duration, run: F) -> ModelRunResult
+where
+  F: FnOnce() -> Fut,
+  Fut: Future<Output = Result<LlmResponse, String>>,
+{
+  let start = Instant::now();
+  let outcome = match tokio::time::timeout(timeout, run()).await {
+    Ok(outcome) => outcome,
+    Err(_) => Err(format!("Timed out after {}s", timeout.as_secs())),
+  };
+  let duration_ms = start.elapsed().as_millis() as u64;
+
+  match outcome {
+    Ok(response) => ModelRunResult {
+      model,
+      response: Some(response.text),
+      error: None,
+      duration_ms,
+      prompt_tokens: response.prompt_tokens,
+      completion_tokens: response.completion_tokens,
+    },
+    Err(e) => {
+      log::warn!("[compare_models] {} failed: {}", model.as_str(), e);
+      ModelRunResult {
+        model,
+        response: None,
+        error: Some(e),
+        duration_ms,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn mock_response(text: &str, prompt_tokens: u64, completion_tokens: u64) -> LlmResponse {
+    LlmResponse {
+      text: text.to_string(),
+      prompt_tokens,
+      completion_tokens,
+    }
+  }
+
+  #[test]
+  fn test_both_model_results_are_returned_with_their_own_usage() {
+    let comparison = tauri::async_runtime::block_on(compare_with(
+      "Hello".to_string(),
+      ModelSelection::Local,
+      ModelSelection::Fast,
+      Duration::from_secs(5),
+      |prompt, model| async move {
+        assert_eq!(prompt, "Hello");
+        match model {
+          ModelSelection::Local => {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            Ok(mock_response("local answer", 12, 34))
+          }
+          _ => Ok(mock_response("cloud answer", 56, 78)),
+        }
+      },
+    ));
+
+    assert_eq!(comparison.prompt, "Hello");
+    let (a, b) = (comparison.model_a, comparison.model_b);
+    assert_eq!(a.model, ModelSelection::Local);
+    assert_eq!(a.response.as_deref(), Some("local answer"));
+    assert_eq!((a.prompt_tokens, a.completion_tokens), (12, 34));
+    assert!(a.duration_ms >= 5);
+    assert_eq!(b.model, ModelSelection::Fast);
+    assert_eq!(b.response.as_deref(), Some("cloud answer"));
+    assert_eq!((b.prompt_tokens, b.completion_tokens), (56, 78));
+  }
+
+  #[test]
+  fn test_one_failing_model_does_not_hide_the_other() {
+    let comparison = tauri::async_runtime::block_on(compare_with(
+      "Hello".to_string(),
+      ModelSelection::Pro,
+      ModelSelection::Ollama,
+      Duration::from_millis(50),
+      |_, model| async move {
+        match model {
+          ModelSelection::Pro => {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(mock_response("too late", 1, 1))
+          }
+          _ => Ok(mock_response("ollama answer", 3, 4)),
+        }
+      },
+    ));
+
+    let (a, b) = (comparison.model_a, comparison.model_b);
+    assert!(a.response.is_none());
+    assert!(a.error.as_deref().is_some_and(|e| e.contains("Timed out")));
+    assert_eq!((a.prompt_tokens, a.completion_tokens), (0, 0));
+    assert_eq!(b.response.as_deref(), Some("ollama answer"));
+    assert_eq!((b.prompt_tokens, b.completion_tokens), (3, 4));
+  }
+}
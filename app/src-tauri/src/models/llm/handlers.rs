@@ -1,13 +1,134 @@
+use crate::constants::{RESUME_SUMMARY_MAX_MESSAGE_CHARS, RESUME_SUMMARY_MIN_MESSAGES};
 use crate::db::conversations::{
-  create_attachments, add_attachments, add_message, add_message_with_id, update_conversation_name,
+  create_attachments, add_attachments, add_message_with_id, get_conversation,
+  get_conversation_streaming, get_conversation_summary, get_messages, messages_since_boundary,
+  save_conversation_summary, save_message_content, truncate_after_message,
+  update_conversation_name,
 };
+use crate::auth::commands::get_user_name;
 use crate::db::memory::find_similar_memories;
 use crate::events::{emitter::emit, types::*};
-use crate::models::llm::{client::generate, prompts::get_prompt, schemas::get_schema, types::LlmRequest};
+use crate::models::llm::{client::generate, prompts::get_prompt, schemas::get_schema, types::{LlmError, LlmRequest, ProviderPolicy}};
 use tauri::AppHandle;
 
+/// Fill in the hud_chat system prompt placeholders. Lines mentioning the
+/// user's name are dropped when nobody is signed in.
+fn build_system_prompt(template: &str, current_date_time: &str, user_name: Option<&str>) -> String {
+  let template = match user_name {
+    Some(name) => template.replace("{user_name}", name),
+    None => template
+      .lines()
+      .filter(|line| !line.contains("{user_name}"))
+      .collect::<Vec<_>>()
+      .join("\n"),
+  };
+  template.replace("{currentDateTime}", current_date_time)
+}
+
+/// Append a resume summary to the system prompt so the model can re-orient
+fn with_conversation_summary(system_prompt: String, summary: Option<&str>) -> String {
+  match summary {
+    Some(summary) => format!(
+      "{}\n\nPreviously in this conversation:\n{}",
+      system_prompt, summary
+    ),
+    None => system_prompt,
+  }
+}
+
+/// Prepare a long conversation for continuation by generating (or reusing) a
+/// brief summary. The next chat turn sends it in the system prompt in place of the
+/// earlier messages; stored history is left untouched.
+#[tauri::command]
+pub async fn resume_conversation(
+  app_handle: AppHandle,
+  conversation_id: String,
+) -> Result<Option<String>, String> {
+  let conversation = get_conversation(app_handle.clone(), conversation_id.clone()).await?;
+  if conversation.message_count < RESUME_SUMMARY_MIN_MESSAGES {
+    return Ok(None);
+  }
+
+  if let Some(summary) = get_conversation_summary(&app_handle, &conversation_id)? {
+    return Ok(Some(summary));
+  }
+
+  log::info!(
+    "[resume_conversation] Summarizing conversation {} ({} messages)",
+    conversation_id,
+    conversation.message_count
+  );
+
+  let messages = get_messages(app_handle.clone(), conversation_id.clone()).await?;
+  let transcript = messages_since_boundary(messages)
+    .iter()
+    .map(|msg| {
+      let content: String = msg.content.chars().take(RESUME_SUMMARY_MAX_MESSAGE_CHARS).collect();
+      format!("{}: {}", msg.role.as_str(), content)
+    })
+    .collect::<Vec<_>>()
+    .join("\n\n");
+
+  let system_prompt = get_prompt("summarize_conversation")
+    .ok_or("Missing system prompt: summarize_conversation")?
+    .to_string();
+  let request = LlmRequest::new(transcript)
+    .with_system_prompt(Some(system_prompt))
+    .with_use_thinking(Some(false))
+    .with_stream(Some(false));
+
+  let summary = generate(app_handle.clone(), request, ProviderPolicy::Default)
+    .await
+    .map_err(|e| {
+      log::error!("[resume_conversation] Failed to generate summary: {}", e);
+      "Failed to summarize conversation".to_string()
+    })?
+    .trim()
+    .to_string();
+
+  save_conversation_summary(&app_handle, &conversation_id, &summary)?;
+  Ok(Some(summary))
+}
+
+/// Build the generation request for a hud chat turn. A resume summary stands in for
+/// the history before the current message.
+fn build_chat_request(
+  event: &HudChatEvent,
+  user_prompt: String,
+  system_prompt: String,
+  resume_summary: Option<&str>,
+  stream: bool,
+) -> LlmRequest {
+  LlmRequest::new(user_prompt)
+    .with_system_prompt(Some(with_conversation_summary(system_prompt, resume_summary)))
+    .with_conv_id(Some(event.conv_id.clone()))
+    .with_use_thinking(Some(false))
+    .with_stream(Some(stream))
+    .with_current_message_id(Some(event.message_id.clone()))
+    .with_history_summarized(resume_summary.is_some())
+}
+
+/// Returned when a chat message has no text and no attachments
+pub const EMPTY_MESSAGE_ERROR: &str = "Message is empty";
+
+/// Reject blank messages unless they carry attachments (e.g. a screenshot with no question)
+fn validate_user_message(event: &HudChatEvent) -> Result<(), String> {
+  if event.text.trim().is_empty() && event.attachments.is_empty() {
+    return Err(EMPTY_MESSAGE_ERROR.to_string());
+  }
+  Ok(())
+}
+
 #[tauri::command]
 pub async fn handle_hud_chat(app_handle: AppHandle, event: HudChatEvent) -> Result<String, String> {
+  validate_user_message(&event)?;
+
+  // Pick up a resume summary before the new message makes it stale
+  let resume_summary = get_conversation_summary(&app_handle, &event.conv_id).unwrap_or_else(|e| {
+    log::warn!("[hud_chat] Failed to load conversation summary: {}", e);
+    None
+  });
+
   // Save the user message to the database
   let _user_message = match add_message_with_id(
     &app_handle,
@@ -33,15 +154,6 @@ pub async fn handle_hud_chat(app_handle: AppHandle, event: HudChatEvent) -> Resu
   };
   let _ = emit(EXTRACT_INTERACTIVE_MEMORY, extract_event);
 
-  // Create prompt
-  let system_prompt_template = match get_prompt("hud_chat") {
-    Some(template) => template,
-    None => {
-      log::error!("[hud_chat] Failed to get prompt template for 'hud_chat'");
-      return Err("Failed to get prompt template for 'hud_chat'".into());
-    }
-  };
-
   // Create attachments and save them to the database
   let attachments = create_attachments(
     &app_handle.clone(),
@@ -78,10 +190,52 @@ pub async fn handle_hud_chat(app_handle: AppHandle, event: HudChatEvent) -> Resu
     }
   }
 
+  respond_to_message(app_handle, &event, resume_summary).await
+}
+
+/// Delete everything after a user message and answer it again, e.g. after `edit_message`
+#[tauri::command]
+pub async fn regenerate_from(app_handle: AppHandle, message_id: String) -> Result<String, String> {
+  let message = truncate_after_message(&app_handle, &message_id, None).await?;
+
+  let resume_summary = get_conversation_summary(&app_handle, &message.conversation_id)
+    .unwrap_or_else(|e| {
+      log::warn!("[hud_chat] Failed to load conversation summary: {}", e);
+      None
+    });
+
+  let event = HudChatEvent {
+    text: message.content,
+    timestamp: chrono::Utc::now().to_rfc3339(),
+    conv_id: message.conversation_id,
+    message_id: message.id,
+    // Attachments were saved with the original message
+    attachments: Vec::new(),
+  };
+  respond_to_message(app_handle, &event, resume_summary).await
+}
+
+/// Generate and save the assistant's reply to a user message that is already saved
+async fn respond_to_message(
+  app_handle: AppHandle,
+  event: &HudChatEvent,
+  resume_summary: Option<String>,
+) -> Result<String, String> {
+  // Create prompt
+  let system_prompt_template = match get_prompt("hud_chat") {
+    Some(template) => template,
+    None => {
+      log::error!("[hud_chat] Failed to get prompt template for 'hud_chat'");
+      return Err("Failed to get prompt template for 'hud_chat'".into());
+    }
+  };
+
   // Get the current date time YYYY-MM-DD format
   let current_date_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
 
-  let system_prompt = system_prompt_template.replace("{currentDateTime}", &current_date_time);
+  let user_name = get_user_name();
+  let system_prompt =
+    build_system_prompt(system_prompt_template, &current_date_time, user_name.as_deref());
 
   // Get 3 most relevant memories
   let relevant_memories =
@@ -116,18 +270,26 @@ pub async fn handle_hud_chat(app_handle: AppHandle, event: HudChatEvent) -> Resu
 
   log::info!("[hud_chat] Generated user prompt:\n{}", user_prompt);
 
-  // Generate response
-  let request = LlmRequest::new(user_prompt)
-    .with_system_prompt(Some(system_prompt))
-    .with_conv_id(Some(event.conv_id.clone()))
-    .with_use_thinking(Some(false))
-    .with_stream(Some(true))
-    .with_current_message_id(Some(event.message_id.clone()));
+  // Generate response, honoring the conversation's streaming preference
+  let stream = get_conversation_streaming(app_handle.clone(), event.conv_id.clone())
+    .unwrap_or_else(|e| {
+      log::warn!("[hud_chat] Failed to load streaming preference: {}", e);
+      true
+    });
+  // Streamed responses are saved into this message as they arrive
+  let assistant_message_id = uuid::Uuid::new_v4().to_string();
+  let request = build_chat_request(event, user_prompt, system_prompt, resume_summary.as_deref(), stream)
+    .with_response_message_id(Some(assistant_message_id.clone()));
 
-  let response = match generate(app_handle.clone(), request, None).await {
+  let response = match generate(app_handle.clone(), request, ProviderPolicy::Default).await {
     Ok(response) => {
       response
     }
+    // Let the UI tell a warming-up or switching model apart from a real failure
+    Err(e @ (LlmError::ModelLoading | LlmError::ModelSwitching)) => {
+      log::warn!("[hud_chat] Model not ready: {}", e);
+      return Err(e.into());
+    }
     Err(e) => {
       log::error!("[hud_chat] Failed to generate response: {}", e);
       return Err("Failed to generate response".into());
@@ -135,9 +297,10 @@ pub async fn handle_hud_chat(app_handle: AppHandle, event: HudChatEvent) -> Resu
   };
 
   // Save the assistant response to the database
-  if let Err(e) = add_message(
+  if let Err(e) = save_message_content(
     &app_handle,
     event.conv_id.clone(),
+    assistant_message_id,
     "assistant".to_string(),
     response.clone(),
   )
@@ -184,7 +347,7 @@ pub async fn handle_generate_conversation_name(
     .with_use_thinking(Some(false))
     .with_stream(Some(false));
 
-  let generated_name = match generate(app_handle.clone(), request, Some(true)).await {
+  let generated_name = match generate(app_handle.clone(), request, ProviderPolicy::ForceLocal).await {
     Ok(generated) => {
       log::info!("[generate_conversation_name] Generated conversation name");
       generated
@@ -243,3 +406,102 @@ pub async fn handle_generate_conversation_name(
   let _ = emit(RENAME_CONVERSATION, name_event);
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::auth::types::{SupabaseUser, UserInfo};
+
+  #[test]
+  fn test_authenticated_user_name_is_substituted() {
+    let user: SupabaseUser = serde_json::from_value(serde_json::json!({
+      "id": "user-1",
+      "user_metadata": { "full_name": "Ada Lovelace" }
+    }))
+    .unwrap();
+    let user_name = UserInfo::from(&user).full_name;
+
+    let template = get_prompt("hud_chat").unwrap();
+    let prompt = build_system_prompt(template, "2025-01-01 09:00:00", user_name.as_deref());
+    assert!(prompt.contains("The user's name is Ada Lovelace."));
+    assert!(!prompt.contains("{user_name}"));
+  }
+
+  #[test]
+  fn test_resume_summary_is_injected_into_system_prompt() {
+    let prompt = with_conversation_summary(
+      "You are Ambient.".to_string(),
+      Some("The user is planning a trip to Japan."),
+    );
+    assert!(prompt.starts_with("You are Ambient."));
+    assert!(prompt.contains("Previously in this conversation:\nThe user is planning a trip to Japan."));
+
+    let unchanged = with_conversation_summary("You are Ambient.".to_string(), None);
+    assert_eq!(unchanged, "You are Ambient.");
+  }
+
+  fn chat_event(text: &str, attachments: Vec<AttachmentData>) -> HudChatEvent {
+    HudChatEvent {
+      text: text.to_string(),
+      timestamp: "2025-01-01T00:00:00Z".to_string(),
+      conv_id: "conv-1".to_string(),
+      message_id: "msg-1".to_string(),
+      attachments,
+    }
+  }
+
+  #[test]
+  fn test_blank_message_rejected_unless_it_has_attachments() {
+    let blank = chat_event("  \n\t", Vec::new());
+    assert_eq!(validate_user_message(&blank), Err(EMPTY_MESSAGE_ERROR.to_string()));
+
+    let with_image = chat_event(
+      "",
+      vec![AttachmentData {
+        name: "screenshot.png".to_string(),
+        file_type: "image/png".to_string(),
+        data: "iVBORw0KGgo=".to_string(),
+      }],
+    );
+    assert!(validate_user_message(&with_image).is_ok());
+
+    assert!(validate_user_message(&chat_event("hello", Vec::new())).is_ok());
+  }
+
+  #[test]
+  fn test_resume_summary_replaces_earlier_history() {
+    let event = chat_event("what next?", Vec::new());
+    let request = build_chat_request(
+      &event,
+      "what next?".to_string(),
+      "You are Ambient.".to_string(),
+      Some("The user is planning a trip to Japan."),
+      true,
+    );
+    assert!(request.history_summarized);
+    assert!(request
+      .system_prompt
+      .as_deref()
+      .unwrap()
+      .ends_with("Previously in this conversation:\nThe user is planning a trip to Japan."));
+
+    let unsummarized = build_chat_request(
+      &event,
+      "what next?".to_string(),
+      "You are Ambient.".to_string(),
+      None,
+      false,
+    );
+    assert_eq!(unsummarized.stream, Some(false));
+    assert!(!unsummarized.history_summarized);
+    assert_eq!(unsummarized.system_prompt.as_deref(), Some("You are Ambient."));
+  }
+
+  #[test]
+  fn test_user_name_line_dropped_when_signed_out() {
+    let template = get_prompt("hud_chat").unwrap();
+    let prompt = build_system_prompt(template, "2025-01-01 09:00:00", None);
+    assert!(!prompt.contains("user's name"));
+    assert!(prompt.contains("Today is 2025-01-01 09:00:00."));
+  }
+}
@@ -1,7 +1,11 @@
+pub mod circuit_breaker;
 pub mod client;
+pub mod compare;
 pub mod handlers;
 pub mod prompts;
 pub mod providers;
 pub mod schemas;
 pub mod server;
+pub mod shutdown;
 pub mod types;
+pub mod watchdog;
@@ -110,10 +110,21 @@ Examples:
 "How do I sort a list in Python?" → {"name":"Python List Sorting"}
 "What's the capital of France?" → {"name":"France Capital Question"}
 "Help me write a resume" → {"name":"Resume Writing Help"}"#,
+  );
+  map.insert(
+    "summarize_conversation",
+    r#"Summarize this conversation between a user and an AI assistant so the assistant can pick it back up later.
+
+Rules:
+- Write 3-5 sentences in plain prose
+- Cover the user's goals, key facts and decisions, and anything left unresolved
+- Refer to the participants as "the user" and "the assistant"
+- Do not add information that is not in the conversation"#,
   );
   map.insert(
     "hud_chat",
     r#"You are Ambient, a helpful AI assistant. Today is {currentDateTime}.
+The user's name is {user_name}.
 
 You may receive:
 - Memories: Past facts about the user
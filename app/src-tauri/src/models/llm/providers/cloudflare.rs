@@ -1,9 +1,15 @@
-use crate::models::llm::types::{LlmRequest, LlmProvider};
+use crate::models::llm::circuit_breaker::CLOUD_BREAKER;
+use crate::models::llm::providers::{extracted_text_context, PartialResponseWriter};
+use crate::models::llm::shutdown::is_shutting_down;
+use crate::models::llm::types::{LlmError, LlmProvider, LlmRequest, LlmResponse};
 use crate::events::{emitter::emit, types::*};
 use crate::auth::commands::get_access_token_command;
-use crate::db::token_usage::add_token_usage;
+use crate::db::conversations::request_history;
+use crate::db::llm_debug::record_llm_exchange;
+use crate::db::token_usage::{add_token_usage, record_conversation_usage};
 use crate::constants::CLOUDFLARE_COMPLETIONS_WORKER_URL;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::StatusCode;
 use base64::{Engine as _, engine::general_purpose};
 use serde_json::{json, Value};
 use tauri::{AppHandle, Manager};
@@ -29,6 +35,7 @@ async fn build_content(
   user_prompt: String,
   conv_id: &Option<String>,
   current_message_id: &Option<String>,
+  history_summarized: bool,
 ) -> Result<Vec<Value>, String> {
   let mut content = Vec::new();
 
@@ -36,6 +43,9 @@ async fn build_content(
     if let Ok(conv_messages) =
       crate::db::conversations::get_messages(app_handle.clone(), conversation_id.clone()).await
     {
+      let conv_messages =
+        request_history(conv_messages, current_message_id.as_deref(), history_summarized);
+
       // Collect IDs of the most recent images/pdfs across all messages
       let mut valid_attachments = Vec::new();
       for msg in conv_messages.iter().rev() {
@@ -82,13 +92,15 @@ async fn build_content(
                 }
               }
             }
-          } else if attachment.file_type == "ambient/ocr" {
-            // Attach OCR text
-            if let Some(extracted_text) = attachment.extracted_text {
-              content_parts.push(json!({
-                "text": format!("Extracted text from user's screen:\n{}", extracted_text)
-              }));
-            }
+          } else if let Some(text) = attachment
+            .extracted_text
+            .as_deref()
+            .and_then(|t| extracted_text_context(&attachment.file_type, t))
+          {
+            // Attach OCR or transcript text
+            content_parts.push(json!({
+              "text": text
+            }));
           }
         }
 
@@ -107,25 +119,39 @@ async fn build_content(
 
 pub struct CloudflareProvider;
 
+/// Statuses that mean the service itself is struggling, as opposed to a bad request
+fn is_outage(status: StatusCode) -> bool {
+  status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
 #[async_trait::async_trait]
 impl LlmProvider for CloudflareProvider {
   async fn generate(
     &self,
     app_handle: AppHandle,
     request: LlmRequest,
-  ) -> Result<String, String> {
-    // Load user settings to get model selection
-    let settings = crate::settings::service::load_user_settings(app_handle.clone())
-      .await
-      .map_err(|e| format!("Failed to load user settings: {}", e))?;
-    let model = &settings.model_selection.as_str();
+  ) -> Result<LlmResponse, LlmError> {
+    // Use the requested model, falling back to the user's model selection
+    let model_selection = match request.model {
+      Some(model) => model,
+      None => {
+        crate::settings::service::load_user_settings(app_handle.clone())
+          .await
+          .map_err(|e| format!("Failed to load user settings: {}", e))?
+          .model_selection
+      }
+    };
+    let model = &model_selection.as_str();
+    CLOUD_BREAKER.check(model_selection)?;
 
+    let mut writer = PartialResponseWriter::new(&app_handle, &request);
     let should_stream = request.stream.unwrap_or(false);
     let mut content = build_content(
       &app_handle,
       request.prompt.clone(),
       &request.conv_id,
-      &request.current_message_id
+      &request.current_message_id,
+      request.history_summarized,
     ).await?;
 
     // Add user prompt if no current message id is provided
@@ -154,8 +180,7 @@ impl LlmProvider for CloudflareProvider {
       if let Ok(schema_value) = serde_json::from_str::<Value>(&schema_str) {
         body["jsonSchema"] = schema_value;
       } else {
-        // Fallback to json_object
-        body["jsonSchema"] = json!({ "type": "json_object" });
+        return Err(LlmError::InvalidRequest("Invalid JSON schema provided".to_string()));
       }
     }
 
@@ -174,19 +199,27 @@ impl LlmProvider for CloudflareProvider {
         .json(&body)
         .send()
         .await
-        .map_err(|e| format!("Failed to send streaming request: {}", e))?;
+        .map_err(|e| {
+          CLOUD_BREAKER.record(model_selection, false);
+          format!("Failed to send streaming request: {}", e)
+        })?;
 
-      if !resp.status().is_success() {
-        let status = resp.status();
+      let status = resp.status();
+      CLOUD_BREAKER.record(model_selection, !is_outage(status));
+      if !status.is_success() {
         let text = resp.text().await.unwrap_or_default();
         log::error!("Cloudflare streaming error status: {}. Body: {}", status, text);
-        return Err(format!("Cloudflare error {}: {}", status, text));
+        return Err(format!("Cloudflare error {}: {}", status, text).into());
       }
 
       let mut full = String::new();
       let mut buffer = String::new();
       let mut stream = resp.bytes_stream();
       while let Some(chunk) = stream.next().await {
+        if is_shutting_down() {
+          log::info!("[cloudflare] App is quitting, saving the partial response");
+          break;
+        }
         let Ok(chunk) = chunk.map_err(|e| format!("Error reading stream: {}", e)) else {
           log::warn!("Stream chunk error encountered");
           break;
@@ -233,6 +266,7 @@ impl LlmProvider for CloudflareProvider {
               .and_then(|t| t.as_str())
             {
               full.push_str(piece);
+              writer.update(&full).await;
               let _ = emit(
                 CHAT_STREAM,
                 ChatStreamEvent {
@@ -249,6 +283,8 @@ impl LlmProvider for CloudflareProvider {
         }
       }
 
+      writer.finish(&full).await;
+
       // Final event
       let _ = emit(
         CHAT_STREAM,
@@ -260,15 +296,28 @@ impl LlmProvider for CloudflareProvider {
         },
       );
 
-      // Save token usage
-      add_token_usage(
-          app_handle.clone(),
-          model,
-          prompt_tokens,
-          completion_tokens,
-      ).await?;
-
-      Ok(full)
+      // Save token usage. The response is already delivered, so a failure here is only logged.
+      let usage = add_token_usage(app_handle.clone(), model, prompt_tokens, completion_tokens).await;
+      if let Err(e) = usage {
+        log::warn!("[cloudflare] Failed to save token usage: {}", e);
+      }
+      record_conversation_usage(
+        &app_handle,
+        request.conv_id.clone(),
+        "cloudflare",
+        model,
+        prompt_tokens,
+        completion_tokens,
+      )
+      .await;
+
+      record_llm_exchange(&app_handle, request.conv_id.clone(), model, &body, &full).await;
+
+      Ok(LlmResponse {
+        text: full,
+        prompt_tokens,
+        completion_tokens,
+      })
     } else {
       let resp = client
         .post(CLOUDFLARE_COMPLETIONS_WORKER_URL)
@@ -276,13 +325,17 @@ impl LlmProvider for CloudflareProvider {
         .json(&body)
         .send()
         .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
+        .map_err(|e| {
+          CLOUD_BREAKER.record(model_selection, false);
+          format!("Failed to send request: {}", e)
+        })?;
 
       let status = resp.status();
+      CLOUD_BREAKER.record(model_selection, !is_outage(status));
       if !status.is_success() {
         let text = resp.text().await.unwrap_or_default();
         log::error!("Cloudflare error status: {}. Body: {}", status, text);
-        return Err(format!("Cloudflare error {}: {}", status, text));
+        return Err(format!("Cloudflare error {}: {}", status, text).into());
       }
 
       let json: Value = resp
@@ -319,15 +372,35 @@ impl LlmProvider for CloudflareProvider {
           json.as_str().unwrap_or("").to_string()
         });
 
-      // Save token usage
-      add_token_usage(
-          app_handle.clone(),
-          model,
-          prompt_tokens,
-          completion_tokens,
-      ).await?;
-
-      Ok(content)
+      // Save token usage. The response is already delivered, so a failure here is only logged.
+      let usage = add_token_usage(app_handle.clone(), model, prompt_tokens, completion_tokens).await;
+      if let Err(e) = usage {
+        log::warn!("[cloudflare] Failed to save token usage: {}", e);
+      }
+      record_conversation_usage(
+        &app_handle,
+        request.conv_id.clone(),
+        "cloudflare",
+        model,
+        prompt_tokens,
+        completion_tokens,
+      )
+      .await;
+
+      record_llm_exchange(
+        &app_handle,
+        request.conv_id.clone(),
+        model,
+        &body,
+        &json.to_string(),
+      )
+      .await;
+
+      Ok(LlmResponse {
+        text: content,
+        prompt_tokens,
+        completion_tokens,
+      })
     }
   }
 }
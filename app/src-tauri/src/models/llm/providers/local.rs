@@ -1,7 +1,12 @@
-use crate::models::llm::types::{LlmRequest, LlmProvider};
-use crate::db::token_usage::add_token_usage;
-use crate::models::llm::server::{perform_health_check, get_current_server_config};
-use crate::events::{emitter::emit, types::{CHAT_STREAM, ChatStreamEvent}};
+use crate::models::llm::providers::openai_compat::{
+  completion_text, parse_usage, record_completion, stream_completion,
+};
+use crate::models::llm::providers::{extracted_text_context, PartialResponseWriter};
+use crate::models::llm::types::{LlmError, LlmProvider, LlmRequest, LlmResponse};
+use crate::db::conversations::request_history;
+use crate::models::llm::server::{
+  ensure_model_ready, get_current_server_config, send_with_retry, ServerError,
+};
 use serde_json::{json, Value};
 use base64::{Engine as _, engine::general_purpose};
 use tauri::{AppHandle, Manager};
@@ -12,12 +17,13 @@ pub struct LocalProvider;
 const MAX_RECENT_ATTACHMENTS: usize = 3;
 
 /// Build messages according to the OpenAI conversations format
-async fn build_messages(
+pub(super) async fn build_messages(
   app_handle: &AppHandle,
   system_prompt: String,
   user_prompt: String,
   conv_id: &Option<String>,
   current_message_id: &Option<String>,
+  history_summarized: bool,
 ) -> Result<Vec<Value>, String> {
   let mut messages = Vec::new();
 
@@ -30,6 +36,9 @@ async fn build_messages(
     if let Ok(conv_messages) =
       crate::db::conversations::get_messages(app_handle.clone(), conversation_id.clone()).await
     {
+      let conv_messages =
+        request_history(conv_messages, current_message_id.as_deref(), history_summarized);
+
       // Collect IDs of the most recent images/pdfs across all messages
       let mut valid_attachments = Vec::new();
       for msg in conv_messages.iter().rev() {
@@ -97,14 +106,16 @@ async fn build_messages(
                 }
               }
             }
-          } else if attachment.file_type == "ambient/ocr" {
-            // Attach OCR text
-            if let Some(extracted_text) = attachment.extracted_text {
-              content_blocks.push(json!({
-                "type": "text",
-                "text": format!("Extracted text from user's screen:\n{}", extracted_text)
-              }));
-            }
+          } else if let Some(text) = attachment
+            .extracted_text
+            .as_deref()
+            .and_then(|t| extracted_text_context(&attachment.file_type, t))
+          {
+            // Attach OCR or transcript text
+            content_blocks.push(json!({
+              "type": "text",
+              "text": text
+            }));
           }
 
         }
@@ -127,16 +138,19 @@ impl LlmProvider for LocalProvider {
     &self,
     app_handle: AppHandle,
     request: LlmRequest,
-  ) -> Result<String, String> {
+  ) -> Result<LlmResponse, LlmError> {
     log::info!("[llama_server] Starting chat completion generation");
-    let config = get_current_server_config(&app_handle).map_err(|e| e.to_string())?;
+    let config = get_current_server_config()?;
 
-    // Check if server is healthy first
-    if let Err(e) = perform_health_check(&config).await {
-      return Err(format!("Server health check failed: {}", e));
+    // Check if server is healthy first, giving a model that is still loading a moment to finish
+    match ensure_model_ready(&config).await {
+      Ok(()) => {}
+      Err(ServerError::ModelLoading) => return Err(LlmError::ModelLoading),
+      Err(e) => return Err(format!("Server health check failed: {}", e).into()),
     }
 
-    let system_prompt = request.system_prompt.unwrap_or("You are a helpful assistant".to_string());
+    let mut writer = PartialResponseWriter::new(&app_handle, &request);
+    let system_prompt = request.system_prompt.clone().unwrap_or("You are a helpful assistant".to_string());
     let should_stream = request.stream.unwrap_or(false);
     let enable_thinking = request.use_thinking.unwrap_or(true);
     
@@ -145,14 +159,15 @@ impl LlmProvider for LocalProvider {
       system_prompt,
       request.prompt.clone(),
       &request.conv_id, 
-      &request.current_message_id
+      &request.current_message_id,
+      request.history_summarized,
     ).await?;
 
     // Add user prompt if no current message id is provided
     if request.current_message_id.is_none() {
       messages.push(json!({
         "role": "user",
-        "content": request.prompt
+        "content": request.prompt.clone()
       }));
     }
 
@@ -169,16 +184,17 @@ impl LlmProvider for LocalProvider {
         "presence_penalty": 1.5,
         "max_tokens": 32768
     });
+    request.sampling.apply(&mut request_body)?;
 
     // Add JSON schema if provided
-    if let Some(schema) = request.json_schema {
-      if let Ok(schema_value) = serde_json::from_str::<Value>(&schema) {
+    if let Some(schema) = &request.json_schema {
+      if let Ok(schema_value) = serde_json::from_str::<Value>(schema) {
         request_body["response_format"] = json!({
             "type": "json_object",
             "schema": schema_value
         });
       } else {
-        return Err("Invalid JSON schema provided".to_string());
+        return Err(LlmError::InvalidRequest("Invalid JSON schema provided".to_string()));
       }
     }
 
@@ -190,9 +206,6 @@ impl LlmProvider for LocalProvider {
     let client = reqwest::Client::new();
     let completion_url = format!("{}/v1/chat/completions", config.base_url());
 
-    let mut prompt_tokens = 0u64;
-    let mut completion_tokens = 0u64;
-
     if should_stream {
       // Handle streaming response
       let response = client
@@ -210,142 +223,64 @@ impl LlmProvider for LocalProvider {
           .text()
           .await
           .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("Server returned error {}: {}", status, error_text));
-      }
-
-      // Process streaming response
-      let mut full_response = String::new();
-      let mut stream = response.bytes_stream();
-
-      use tokio_stream::StreamExt;
-
-      while let Some(chunk_result) = stream.next().await {
-        match chunk_result {
-          Ok(chunk) => {
-            let chunk_str = String::from_utf8_lossy(&chunk);
-
-            // Parse SSE format
-            for line in chunk_str.lines() {
-              if line.starts_with("data: ") {
-                let data = &line[6..]; // Remove "data: " prefix
-
-                if data == "[DONE]" {
-                  break;
-                }
-
-                if let Ok(json_data) = serde_json::from_str::<Value>(data) {
-                  if let Some(choices) = json_data["choices"].as_array() {
-                    if let Some(choice) = choices.get(0) {
-                      // Check if stream is finished
-                      if let Some(finish_reason) = choice["finish_reason"].as_str() {
-                        if finish_reason == "stop" {
-                          // Save token usage
-                          if let Some(timings) = json_data.get("timings") {
-                            prompt_tokens = timings["prompt_n"].as_u64().unwrap_or(0);
-                            completion_tokens = timings["predicted_n"].as_u64().unwrap_or(0);
-                          }
-                          break;
-                        }
-                      }
-
-                      // Process delta content if available
-                      if let Some(delta) = choice["delta"].as_object() {
-                        if let Some(content) = delta.get("content").and_then(|c| c.as_str()) {
-                          full_response.push_str(content);
-
-                          // Emit stream event to frontend
-                          let stream_data = ChatStreamEvent {
-                            delta: content.to_string(),
-                            is_finished: false,
-                            full_response: full_response.clone(),
-                            conv_id: request.conv_id.clone(),
-                          };
-
-                          if let Err(e) = emit(CHAT_STREAM, stream_data) {
-                            log::error!("[llama_server] Failed to emit stream event: {}", e);
-                          }
-                        }
-                        // If no content in delta, just continue (this is normal for some chunks)
-                      }
-                    }
-                  }
-                }
-              }
-            }
-          }
-          Err(e) => {
-            return Err(format!("Error reading stream: {}", e));
-          }
-        }
-      }
-
-      // Emit final stream completion event
-      let final_stream_data = ChatStreamEvent {
-        delta: "".to_string(),
-        is_finished: true,
-        full_response: full_response.clone(),
-        conv_id: request.conv_id.clone(),
-      };
-
-      if let Err(e) = emit(CHAT_STREAM, final_stream_data) {
-        log::error!("[llama_server] Failed to emit final stream event: {}", e);
+        return Err(format!("Server returned error {}: {}", status, error_text).into());
       }
 
-      // Save token usage
-      add_token_usage(
-          app_handle.clone(),
-          "local",
-          prompt_tokens,
-          completion_tokens,
-      ).await?;
-
-      Ok(full_response)
+      let completion =
+        stream_completion(response, &mut writer, request.conv_id.clone(), "llama_server").await?;
+      record_completion(
+        &app_handle,
+        &request,
+        "local",
+        "local",
+        &request_body,
+        &completion.text,
+        &completion,
+      )
+      .await;
+
+      Ok(completion)
     } else {
-      // Handle non-streaming response
-      let response = client
-        .post(&completion_url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", config.api_key))
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-
-      if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response
-          .text()
-          .await
-          .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("Server returned error {}: {}", status, error_text));
-      }
+      // Handle non-streaming response, retrying transient server failures
+      let (response, retries) = send_with_retry(|| {
+        client
+          .post(&completion_url)
+          .header("Content-Type", "application/json")
+          .header("Authorization", format!("Bearer {}", config.api_key))
+          .json(&request_body)
+      })
+      .await?;
 
       let result: Value = response
         .json()
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-      // Extract the generated content
-      let generated_text = result["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or("No content in response")?
-        .to_string();
-
-      // Extract token usage
-      if let Some(timings) = result.get("timings") {
-        prompt_tokens = timings["prompt_n"].as_u64().unwrap_or(0);
-        completion_tokens = timings["predicted_n"].as_u64().unwrap_or(0);
-      }
-
-      // Save token usage
-      add_token_usage(
-          app_handle.clone(),
-          "local",
-          prompt_tokens,
-          completion_tokens,
-      ).await?;
-
-      Ok(generated_text)
+      let (prompt_tokens, completion_tokens) = parse_usage(&result).unwrap_or_default();
+      let completion = LlmResponse {
+        text: completion_text(&result)?,
+        prompt_tokens,
+        completion_tokens,
+      };
+      log::info!(
+        "[llama_server] Generated {} tokens at {:.1} tokens/s ({} retries)",
+        completion_tokens,
+        result["timings"]["predicted_per_second"].as_f64().unwrap_or(0.0),
+        retries
+      );
+
+      record_completion(
+        &app_handle,
+        &request,
+        "local",
+        "local",
+        &request_body,
+        &result.to_string(),
+        &completion,
+      )
+      .await;
+
+      Ok(completion)
     }
   }
 }
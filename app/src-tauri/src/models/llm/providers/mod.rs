@@ -1,2 +1,85 @@
 pub mod local;
 pub mod cloudflare;
+pub mod ollama;
+mod openai_compat;
+
+use crate::constants::STREAM_SAVE_INTERVAL;
+use crate::db::conversations::save_message_content;
+use crate::models::llm::types::LlmRequest;
+use std::time::Instant;
+use tauri::AppHandle;
+
+/// Context text for attachments that carry extracted text instead of a file
+pub fn extracted_text_context(file_type: &str, extracted_text: &str) -> Option<String> {
+  match file_type {
+    "ambient/ocr" => Some(format!("Extracted text from user's screen:\n{}", extracted_text)),
+    "ambient/audio-transcript" => Some(format!("Transcript of user's audio:\n{}", extracted_text)),
+    _ => None,
+  }
+}
+
+/// Saves a streaming response into its message row every `STREAM_SAVE_INTERVAL`,
+/// so a crash mid-stream keeps what was generated so far
+pub struct PartialResponseWriter {
+  app_handle: AppHandle,
+  conv_id: Option<String>,
+  message_id: Option<String>,
+  last_saved: Instant,
+}
+
+impl PartialResponseWriter {
+  pub fn new(app_handle: &AppHandle, request: &LlmRequest) -> Self {
+    Self {
+      app_handle: app_handle.clone(),
+      conv_id: request.conv_id.clone(),
+      message_id: request.response_message_id.clone(),
+      last_saved: Instant::now(),
+    }
+  }
+
+  /// Save the response so far if enough time has passed since the last save
+  pub async fn update(&mut self, full_response: &str) {
+    if self.last_saved.elapsed() >= STREAM_SAVE_INTERVAL {
+      self.save(full_response).await;
+    }
+  }
+
+  /// Save the complete response once the stream is done
+  pub async fn finish(&mut self, full_response: &str) {
+    self.save(full_response).await;
+  }
+
+  async fn save(&mut self, full_response: &str) {
+    let (Some(conv_id), Some(message_id)) = (&self.conv_id, &self.message_id) else {
+      return;
+    };
+    if let Err(e) = save_message_content(
+      &self.app_handle,
+      conv_id.clone(),
+      message_id.clone(),
+      "assistant".to_string(),
+      full_response.to_string(),
+    )
+    .await
+    {
+      log::warn!("[llm] Failed to save partial response: {}", e);
+    }
+    self.last_saved = Instant::now();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_audio_transcript_is_formatted_like_ocr() {
+    let text = extracted_text_context("ambient/audio-transcript", "remind me at noon").unwrap();
+    assert_eq!(text, "Transcript of user's audio:\nremind me at noon");
+
+    let ocr = extracted_text_context("ambient/ocr", "File  Edit  View").unwrap();
+    assert!(ocr.starts_with("Extracted text from user's screen:"));
+
+    assert!(extracted_text_context("image/png", "ignored").is_none());
+  }
+}
@@ -0,0 +1,156 @@
+use crate::models::llm::providers::openai_compat::{
+  completion_text, parse_usage, record_completion, stream_completion,
+};
+use crate::models::llm::providers::{local::build_messages, PartialResponseWriter};
+use crate::models::llm::types::{LlmError, LlmProvider, LlmRequest, LlmResponse};
+use crate::constants::{OLLAMA_DEFAULT_BASE_URL, OLLAMA_DEFAULT_MODEL};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+pub struct OllamaProvider;
+
+/// Resolve the OpenAI-compatible completions endpoint for an Ollama server
+fn completions_url(base_url: Option<&str>) -> String {
+  let base = base_url
+    .map(|url| url.trim().trim_end_matches('/'))
+    .filter(|url| !url.is_empty())
+    .unwrap_or(OLLAMA_DEFAULT_BASE_URL);
+  format!("{}/v1/chat/completions", base)
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for OllamaProvider {
+  async fn generate(
+    &self,
+    app_handle: AppHandle,
+    request: LlmRequest,
+  ) -> Result<LlmResponse, LlmError> {
+    log::info!("[ollama] Starting chat completion generation");
+    let settings = crate::settings::service::load_user_settings(app_handle.clone())
+      .await
+      .map_err(|e| format!("Failed to load user settings: {}", e))?;
+    let completion_url = completions_url(settings.ollama_base_url.as_deref());
+    let model = settings
+      .ollama_model
+      .unwrap_or_else(|| OLLAMA_DEFAULT_MODEL.to_string());
+
+    let mut writer = PartialResponseWriter::new(&app_handle, &request);
+    let system_prompt = request.system_prompt.clone().unwrap_or("You are a helpful assistant".to_string());
+    let should_stream = request.stream.unwrap_or(false);
+
+    let mut messages = build_messages(
+      &app_handle,
+      system_prompt,
+      request.prompt.clone(),
+      &request.conv_id,
+      &request.current_message_id,
+      request.history_summarized,
+    ).await?;
+
+    // Add user prompt if no current message id is provided
+    if request.current_message_id.is_none() {
+      messages.push(json!({
+        "role": "user",
+        "content": request.prompt.clone()
+      }));
+    }
+
+    // Build request body
+    let mut request_body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": should_stream,
+    });
+    if should_stream {
+      request_body["stream_options"] = json!({ "include_usage": true });
+    }
+    // Ollama's OpenAI endpoint may ignore top_k and repeat_penalty
+    request.sampling.apply(&mut request_body)?;
+
+    // Add JSON schema if provided
+    if let Some(schema) = &request.json_schema {
+      if let Ok(schema_value) = serde_json::from_str::<Value>(schema) {
+        request_body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": {
+              "name": "response",
+              "schema": schema_value
+            }
+        });
+      } else {
+        return Err(LlmError::InvalidRequest("Invalid JSON schema provided".to_string()));
+      }
+    }
+
+    let client = reqwest::Client::new();
+    let response = client
+      .post(&completion_url)
+      .header("Content-Type", "application/json")
+      .json(&request_body)
+      .send()
+      .await
+      .map_err(|e| format!("Failed to reach Ollama at {}: {}", completion_url, e))?;
+
+    if !response.status().is_success() {
+      let status = response.status();
+      let error_text = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+      return Err(format!("Ollama returned error {}: {}", status, error_text).into());
+    }
+
+    let (completion, raw_response) = if should_stream {
+      let completion =
+        stream_completion(response, &mut writer, request.conv_id.clone(), "ollama").await?;
+      let raw_response = completion.text.clone();
+      (completion, raw_response)
+    } else {
+      let result: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+      let (prompt_tokens, completion_tokens) = parse_usage(&result).unwrap_or_default();
+      let completion = LlmResponse {
+        text: completion_text(&result)?,
+        prompt_tokens,
+        completion_tokens,
+      };
+      (completion, result.to_string())
+    };
+
+    record_completion(
+      &app_handle,
+      &request,
+      "ollama",
+      &model,
+      &request_body,
+      &raw_response,
+      &completion,
+    )
+    .await;
+
+    Ok(completion)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_completions_url_uses_configured_base() {
+    assert_eq!(
+      completions_url(None),
+      "http://localhost:11434/v1/chat/completions"
+    );
+    assert_eq!(
+      completions_url(Some("http://gpu-box:11434/")),
+      "http://gpu-box:11434/v1/chat/completions"
+    );
+    assert_eq!(
+      completions_url(Some("  ")),
+      "http://localhost:11434/v1/chat/completions"
+    );
+  }
+}
@@ -0,0 +1,215 @@
+use crate::db::llm_debug::record_llm_exchange;
+use crate::db::token_usage::{add_token_usage, record_conversation_usage};
+use crate::events::{emitter::emit, types::{CHAT_STREAM, ChatStreamEvent}};
+use crate::models::llm::providers::PartialResponseWriter;
+use crate::models::llm::shutdown::is_shutting_down;
+use crate::models::llm::types::{LlmError, LlmRequest, LlmResponse};
+use serde_json::Value;
+use tauri::AppHandle;
+use tokio_stream::StreamExt;
+
+/// Read prompt and completion token counts from a completions chunk or response.
+/// OpenAI servers report an `usage` object, llama.cpp reports `timings`.
+pub(super) fn parse_usage(value: &Value) -> Option<(u64, u64)> {
+  if let Some(usage) = value.get("usage").filter(|u| u.is_object()) {
+    return Some((
+      usage["prompt_tokens"].as_u64().unwrap_or(0),
+      usage["completion_tokens"].as_u64().unwrap_or(0),
+    ));
+  }
+  let timings = value.get("timings")?;
+  Some((
+    timings["prompt_n"].as_u64().unwrap_or(0),
+    timings["predicted_n"].as_u64().unwrap_or(0),
+  ))
+}
+
+/// Text of a non-streaming completions response
+pub(super) fn completion_text(result: &Value) -> Result<String, String> {
+  result["choices"][0]["message"]["content"]
+    .as_str()
+    .map(str::to_string)
+    .ok_or_else(|| "No content in response".to_string())
+}
+
+/// Splits a server-sent event stream into `data:` payloads. Chunks can end mid-line, or
+/// mid-character, so the incomplete tail is kept as bytes until the rest arrives.
+#[derive(Default)]
+struct SseBuffer {
+  buffer: Vec<u8>,
+}
+
+impl SseBuffer {
+  fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+    self.buffer.extend_from_slice(chunk);
+    let mut payloads = Vec::new();
+    while let Some(newline_idx) = self.buffer.iter().position(|&b| b == b'\n') {
+      let line_bytes: Vec<u8> = self.buffer.drain(..=newline_idx).collect();
+      let line = String::from_utf8_lossy(&line_bytes);
+      if let Some(data) = line.trim().strip_prefix("data:") {
+        payloads.push(data.trim_start().to_string());
+      }
+    }
+    payloads
+  }
+}
+
+/// Read a streaming completions response, saving it as it arrives and emitting
+/// `CHAT_STREAM` events for each delta. `tag` prefixes log lines.
+pub(super) async fn stream_completion(
+  response: reqwest::Response,
+  writer: &mut PartialResponseWriter,
+  conv_id: Option<String>,
+  tag: &str,
+) -> Result<LlmResponse, LlmError> {
+  let mut completion = LlmResponse::default();
+  let mut sse = SseBuffer::default();
+  let mut stream = response.bytes_stream();
+
+  'read: while let Some(chunk) = stream.next().await {
+    if is_shutting_down() {
+      log::info!("[{}] App is quitting, saving the partial response", tag);
+      break;
+    }
+    let chunk = match chunk {
+      Ok(chunk) => chunk,
+      // Another provider can't take over once deltas were shown and saved
+      Err(e) if !completion.text.is_empty() => {
+        writer.finish(&completion.text).await;
+        return Err(LlmError::Interrupted(format!("Error reading stream: {}", e)));
+      }
+      Err(e) => return Err(format!("Error reading stream: {}", e).into()),
+    };
+
+    for data in sse.push(&chunk) {
+      if data == "[DONE]" {
+        break 'read;
+      }
+      let Ok(json_data) = serde_json::from_str::<Value>(&data) else {
+        log::warn!("[{}] Failed to parse line as JSON: {}", tag, data);
+        continue;
+      };
+
+      // Usage arrives with the last chunk, which may have no content
+      if let Some((prompt, completion_tokens)) = parse_usage(&json_data) {
+        completion.prompt_tokens = prompt;
+        completion.completion_tokens = completion_tokens;
+      }
+
+      let Some(content) = json_data["choices"][0]["delta"]["content"].as_str() else {
+        continue;
+      };
+      if content.is_empty() {
+        continue;
+      }
+      completion.text.push_str(content);
+      writer.update(&completion.text).await;
+
+      let stream_data = ChatStreamEvent {
+        delta: content.to_string(),
+        is_finished: false,
+        full_response: completion.text.clone(),
+        conv_id: conv_id.clone(),
+      };
+      if let Err(e) = emit(CHAT_STREAM, stream_data) {
+        log::error!("[{}] Failed to emit stream event: {}", tag, e);
+      }
+    }
+  }
+
+  writer.finish(&completion.text).await;
+
+  let final_stream_data = ChatStreamEvent {
+    delta: "".to_string(),
+    is_finished: true,
+    full_response: completion.text.clone(),
+    conv_id,
+  };
+  if let Err(e) = emit(CHAT_STREAM, final_stream_data) {
+    log::error!("[{}] Failed to emit final stream event: {}", tag, e);
+  }
+
+  Ok(completion)
+}
+
+/// Save token usage and the debug exchange for a finished completion. The response is
+/// already delivered, so failures are logged rather than returned.
+pub(super) async fn record_completion(
+  app_handle: &AppHandle,
+  request: &LlmRequest,
+  provider: &str,
+  model: &str,
+  request_body: &Value,
+  raw_response: &str,
+  completion: &LlmResponse,
+) {
+  if let Err(e) = add_token_usage(
+    app_handle.clone(),
+    provider,
+    completion.prompt_tokens,
+    completion.completion_tokens,
+  )
+  .await
+  {
+    log::warn!("[llm] Failed to save token usage: {}", e);
+  }
+  record_conversation_usage(
+    app_handle,
+    request.conv_id.clone(),
+    provider,
+    model,
+    completion.prompt_tokens,
+    completion.completion_tokens,
+  )
+  .await;
+
+  record_llm_exchange(
+    app_handle,
+    request.conv_id.clone(),
+    provider,
+    request_body,
+    raw_response,
+  )
+  .await;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_usage_is_read_from_openai_and_llama_formats() {
+    let openai = json!({
+      "choices": [],
+      "usage": { "prompt_tokens": 42, "completion_tokens": 7, "total_tokens": 49 }
+    });
+    assert_eq!(parse_usage(&openai), Some((42, 7)));
+
+    let llama = json!({
+      "choices": [{ "finish_reason": "stop", "delta": {} }],
+      "timings": { "prompt_n": 12, "predicted_n": 30, "predicted_per_second": 40.5 }
+    });
+    assert_eq!(parse_usage(&llama), Some((12, 30)));
+
+    assert_eq!(parse_usage(&json!({ "choices": [], "usage": null })), None);
+  }
+
+  #[test]
+  fn test_sse_payloads_split_across_chunks_are_joined() {
+    let mut sse = SseBuffer::default();
+    assert_eq!(sse.push(b"data: {\"a\":"), Vec::<String>::new());
+    assert_eq!(sse.push(b"1}\n\ndata: [DONE]\n"), vec!["{\"a\":1}", "[DONE]"]);
+    assert_eq!(sse.push(b": keep-alive\n"), Vec::<String>::new());
+  }
+
+  #[test]
+  fn test_sse_characters_split_across_chunks_are_kept() {
+    let mut sse = SseBuffer::default();
+    let event = "data: café\n".as_bytes();
+    // Split between the two bytes of "é"
+    let split = event.len() - 2;
+    assert_eq!(sse.push(&event[..split]), Vec::<String>::new());
+    assert_eq!(sse.push(&event[split..]), vec!["café"]);
+  }
+}
@@ -1,4 +1,5 @@
 use once_cell::sync::Lazy;
+use serde_json::Value;
 use std::collections::HashMap;
 
 // Use Lazy to initialize the HashMap only once
@@ -87,3 +88,94 @@ pub fn get_schema_command(key: String) -> Result<String, String> {
     None => Err(format!("Schema with key '{}' not found.", key)),
   }
 }
+
+/// Check a model response against a JSON schema. Covers the keywords our schemas use
+/// (type, properties, required, additionalProperties, items and enum), since providers
+/// don't all enforce the schema themselves.
+pub fn validate_response(schema: &str, response: &str) -> Result<(), String> {
+  let schema: Value =
+    serde_json::from_str(schema).map_err(|e| format!("Invalid JSON schema provided: {}", e))?;
+  let value: Value =
+    serde_json::from_str(response.trim()).map_err(|e| format!("Response is not valid JSON: {}", e))?;
+  validate_value(&schema, &value, "$")
+}
+
+fn validate_value(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+  if let Some(expected) = schema.get("type") {
+    let allowed: Vec<&str> = match expected {
+      Value::String(t) => vec![t.as_str()],
+      Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+      _ => Vec::new(),
+    };
+    if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+      return Err(format!("{} should be {}", path, allowed.join(" or ")));
+    }
+  }
+
+  if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+    if !options.contains(value) {
+      return Err(format!("{} is not one of the allowed values", path));
+    }
+  }
+
+  if let Value::Object(fields) = value {
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+    for name in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten() {
+      if let Some(name) = name.as_str() {
+        if !fields.contains_key(name) {
+          return Err(format!("{} is missing required field '{}'", path, name));
+        }
+      }
+    }
+    for (name, field) in fields {
+      let field_path = format!("{}.{}", path, name);
+      match properties.and_then(|p| p.get(name)) {
+        Some(field_schema) => validate_value(field_schema, field, &field_path)?,
+        None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+          return Err(format!("{} is not an allowed field", field_path));
+        }
+        None => {}
+      }
+    }
+  }
+
+  if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+    for (i, item) in items.iter().enumerate() {
+      validate_value(item_schema, item, &format!("{}[{}]", path, i))?;
+    }
+  }
+
+  Ok(())
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+  match expected {
+    "object" => value.is_object(),
+    "array" => value.is_array(),
+    "string" => value.is_string(),
+    "integer" => value.is_i64() || value.is_u64(),
+    "number" => value.is_number(),
+    "boolean" => value.is_boolean(),
+    "null" => value.is_null(),
+    _ => true,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_responses_are_checked_against_bundled_schemas() {
+    let schema = get_schema("detect_tasks").unwrap();
+    assert!(validate_response(schema, r#"{"analysis": "done", "completed": [1, 2]}"#).is_ok());
+
+    let missing = validate_response(schema, r#"{"analysis": "done"}"#).unwrap_err();
+    assert!(missing.contains("'completed'"), "{}", missing);
+    let wrong_item = validate_response(schema, r#"{"analysis": "", "completed": ["1"]}"#).unwrap_err();
+    assert!(wrong_item.contains("$.completed[0]"), "{}", wrong_item);
+    let extra = validate_response(schema, r#"{"analysis": "", "completed": [], "x": 1}"#).unwrap_err();
+    assert!(extra.contains("$.x"), "{}", extra);
+    assert!(validate_response(schema, "Sure! Here it is").is_err());
+  }
+}
@@ -1,14 +1,29 @@
 use crate::constants::{
   HEALTH_CHECK_ENDPOINT, HEALTH_CHECK_INTERVAL, MAX_HEALTH_CHECK_RETRIES, MAX_PORT,
-  MAX_PORT_ATTEMPTS, MIN_PORT,
+  MAX_PORT_ATTEMPTS, MIN_PORT, MODEL_LOADING_INTERVAL, MODEL_LOADING_RETRIES,
+  REQUEST_RETRY_ATTEMPTS, REQUEST_RETRY_BASE_DELAY, MIN_CTX_SIZE, MAX_PARALLEL_SEQUENCES,
+  KV_CACHE_QUANTS, SLOTS_ENDPOINT, METRICS_ENDPOINT, RESERVED_SERVER_FLAGS,
+  MODEL_SWITCH_DRAIN_TIMEOUT,
 };
+use crate::events::{emitter::emit, types::{ModelSwitchedEvent, MODEL_SWITCHED}};
+use crate::settings::types::ServerLaunchConfig;
+use crate::models::llm::shutdown::wait_for_generations;
+use crate::models::llm::watchdog::restart_count;
 use crate::setup;
 use rand::Rng;
 use reqwest;
 use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::Receiver;
 use tauri::AppHandle;
-use tauri_plugin_shell::{process::CommandChild, ShellExt};
+use tauri_plugin_shell::{
+  process::{CommandChild, CommandEvent},
+  ShellExt,
+};
+use tokio::sync::oneshot;
 use tokio::time::sleep;
 use uuid::Uuid;
 
@@ -18,14 +33,22 @@ struct ServerState {
   child: Option<CommandChild>,
   port: Option<u16>,
   api_key: Option<String>,
+  text_model_path: Option<String>,
+  mmproj_model_path: Option<String>,
 }
 
 static SERVER_STATE: Mutex<ServerState> = Mutex::new(ServerState {
   child: None,
   port: None,
   api_key: None,
+  text_model_path: None,
+  mmproj_model_path: None,
 });
 
+/// Set while `switch_model` restarts the server so requests fail fast instead of
+/// reaching a server that is shutting down
+static MODEL_SWITCHING: AtomicBool = AtomicBool::new(false);
+
 /// Error types for server operations
 #[derive(Debug)]
 pub enum ServerError {
@@ -33,6 +56,8 @@ pub enum ServerError {
   ConfigError(String),
   ProcessError(String),
   NetworkError(String),
+  ModelLoading,
+  ModelSwitching,
   ServerAlreadyRunning,
   ServerNotRunning,
 }
@@ -44,6 +69,8 @@ impl std::fmt::Display for ServerError {
       ServerError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
       ServerError::ProcessError(msg) => write!(f, "Process error: {}", msg),
       ServerError::NetworkError(msg) => write!(f, "Network error: {}", msg),
+      ServerError::ModelLoading => write!(f, "Model is still loading, please wait"),
+      ServerError::ModelSwitching => write!(f, "Model is switching, please try again in a moment"),
       ServerError::ServerAlreadyRunning => write!(f, "Server is already running"),
       ServerError::ServerNotRunning => write!(f, "Server is not running"),
     }
@@ -65,22 +92,35 @@ pub struct ServerConfig {
   pub port: u16,
   pub api_key: String,
   pub text_model_path: String,
-  pub mmproj_model_path: String,
+  /// Vision projector, only available for the bundled model
+  pub mmproj_model_path: Option<String>,
 }
 
 impl ServerConfig {
-  pub fn new(app_handle: &AppHandle, port: u16) -> Result<Self, ServerError> {
-    // Try to get existing API key from server state first
-    let api_key = {
-      let server_state = SERVER_STATE.lock().unwrap();
-      server_state.api_key.clone()
-    };
-
+  /// Resolve the model files to serve. A `model_override` replaces the bundled model,
+  /// which also drops the bundled vision projector since it only fits that model.
+  pub fn new(
+    app_handle: &AppHandle,
+    port: u16,
+    api_key: Option<String>,
+    model_override: Option<&str>,
+    mmproj_override: Option<&str>,
+  ) -> Result<Self, ServerError> {
     let api_key = api_key.unwrap_or_else(|| {
       let new_key = format!("session-{}", Uuid::new_v4().to_string());
       new_key
     });
 
+    check_model_files(model_override, mmproj_override)?;
+    if let Some(model_path) = model_override {
+      return Ok(ServerConfig {
+        port,
+        api_key,
+        text_model_path: model_path.to_string(),
+        mmproj_model_path: mmproj_override.map(str::to_string),
+      });
+    }
+
     // Get model and mmproj path
     let text_model_path =
       setup::get_vlm_text_model_path(&app_handle).map_err(|e| ServerError::ModelNotFound(e))?;
@@ -112,7 +152,7 @@ impl ServerConfig {
       port,
       api_key,
       text_model_path: text_model_path_str,
-      mmproj_model_path: mmproj_model_path_str,
+      mmproj_model_path: Some(mmproj_model_path_str),
     })
   }
 
@@ -125,6 +165,25 @@ impl ServerConfig {
   }
 }
 
+/// Check a user-chosen model and its optional vision projector exist. A projector is only
+/// meaningful alongside the model it was built for.
+fn check_model_files(
+  model_path: Option<&str>,
+  mmproj_path: Option<&str>,
+) -> Result<(), ServerError> {
+  if model_path.is_none() && mmproj_path.is_some() {
+    return Err(ServerError::ConfigError(
+      "A vision projector needs a model to go with it".to_string(),
+    ));
+  }
+  for path in model_path.into_iter().chain(mmproj_path) {
+    if !std::path::Path::new(path).is_file() {
+      return Err(ServerError::ModelNotFound(format!("Model file does not exist: {}", path)));
+    }
+  }
+  Ok(())
+}
+
 /// Generate a random port number within the acceptable range
 fn generate_random_port() -> u16 {
   let mut rng = rand::thread_rng();
@@ -160,43 +219,132 @@ async fn find_available_port() -> Result<u16, ServerError> {
   )))
 }
 
-/// Get server config using stored port and API key
-pub fn get_current_server_config(app_handle: &AppHandle) -> Result<ServerConfig, ServerError> {
-  let (port, api_key) = {
-    let server_state = SERVER_STATE.lock().unwrap();
-    (server_state.port, server_state.api_key.clone())
-  };
+/// Get server config using stored port, API key and model paths
+pub fn get_current_server_config() -> Result<ServerConfig, ServerError> {
+  if MODEL_SWITCHING.load(Ordering::SeqCst) {
+    return Err(ServerError::ModelSwitching);
+  }
 
-  let port = port.ok_or_else(|| ServerError::ServerNotRunning)?;
-  let api_key = api_key.ok_or_else(|| ServerError::ServerNotRunning)?;
-
-  // Get model path
-  let text_model_path =
-    setup::get_vlm_text_model_path(&app_handle).map_err(|e| ServerError::ModelNotFound(e))?;
-  let mmproj_model_path =
-    setup::get_vlm_mmproj_model_path(&app_handle).map_err(|e| ServerError::ModelNotFound(e))?;
-
-  let text_model_path_str = text_model_path
-    .to_str()
-    .ok_or_else(|| {
-      ServerError::ConfigError(format!("Model path is not valid UTF-8: {:?}", text_model_path))
-    })?
-    .to_string();
-  let mmproj_model_path_str = mmproj_model_path
-    .to_str()
-    .ok_or_else(|| {
-      ServerError::ConfigError(format!("MMProj path is not valid UTF-8: {:?}", mmproj_model_path))
-    })?
-    .to_string();
+  let server_state = SERVER_STATE.lock().unwrap();
+  let port = server_state.port.ok_or(ServerError::ServerNotRunning)?;
+  let api_key = server_state.api_key.clone().ok_or(ServerError::ServerNotRunning)?;
+  let text_model_path = server_state
+    .text_model_path
+    .clone()
+    .ok_or(ServerError::ServerNotRunning)?;
 
   Ok(ServerConfig {
     port,
     api_key,
-    text_model_path: text_model_path_str,
-    mmproj_model_path: mmproj_model_path_str,
+    text_model_path,
+    mmproj_model_path: server_state.mmproj_model_path.clone(),
   })
 }
 
+/// Reject launch settings the server would fail on or that can't fit in memory
+fn validate_launch_config(launch: &ServerLaunchConfig) -> Result<(), ServerError> {
+  if launch.ctx_size < MIN_CTX_SIZE {
+    return Err(ServerError::ConfigError(format!(
+      "Context size must be at least {}, got {}",
+      MIN_CTX_SIZE, launch.ctx_size
+    )));
+  }
+  if launch.parallel_sequences == 0 || launch.parallel_sequences > MAX_PARALLEL_SEQUENCES {
+    return Err(ServerError::ConfigError(format!(
+      "Parallel sequences must be between 1 and {}, got {}",
+      MAX_PARALLEL_SEQUENCES, launch.parallel_sequences
+    )));
+  }
+  if !KV_CACHE_QUANTS.contains(&launch.kv_cache_quant.as_str()) {
+    return Err(ServerError::ConfigError(format!(
+      "Unsupported KV cache quantization: {}",
+      launch.kv_cache_quant
+    )));
+  }
+  // Accept both "--port 8080" and "--port=8080"
+  if let Some(flag) = launch.extra_args.iter().find(|arg| {
+    let flag = arg.split('=').next().unwrap_or(arg);
+    RESERVED_SERVER_FLAGS.contains(&flag)
+  }) {
+    return Err(ServerError::ConfigError(format!(
+      "Extra argument {} is managed by Ambient and can't be overridden",
+      flag
+    )));
+  }
+  Ok(())
+}
+
+/// Build the command line arguments for the llama.cpp server
+fn build_server_args(
+  config: &ServerConfig,
+  launch: &ServerLaunchConfig,
+  chat_template_path: Option<&str>,
+) -> Vec<String> {
+  let ctx_size = launch.ctx_size.to_string();
+  let parallel_sequences = launch.parallel_sequences.to_string();
+  let mut args: Vec<String> = [
+    "-m",
+    &config.text_model_path,
+    "--port",
+    &config.port.to_string(),
+    "--api-key",
+    &config.api_key,
+    "--reasoning-format",
+    "none",
+    "-np", // Number of sequences decoded in parallel
+    &parallel_sequences,
+    "--ctx-size",
+    &ctx_size,
+    "--n-predict",
+    &ctx_size,
+    "--temp",
+    "0.7",
+    "--top-p",
+    "0.8",
+    "--top-k",
+    "20",
+    "--repeat-penalty",
+    "1.0",
+    "--presence-penalty",
+    "1.5",
+    "--seed",
+    "3407",
+    "-ctk", // Quantization for kv cache
+    &launch.kv_cache_quant,
+    "-ctv",
+    &launch.kv_cache_quant,
+    "-fa", // Fast attention
+    if launch.flash_attention { "on" } else { "off" },
+    "--no-webui",
+    "--log-disable",
+    "--offline",
+    "--jinja",
+    "--metrics", // Expose the Prometheus endpoint for get_server_status
+  ]
+  .iter()
+  .map(|s| s.to_string())
+  .collect();
+
+  if let Some(mmproj_path) = &config.mmproj_model_path {
+    args.push("-mm".to_string());
+    args.push(mmproj_path.clone());
+  }
+
+  // Keep model in RAM
+  if launch.mlock {
+    args.push("--mlock".to_string());
+  }
+
+  // Override the template embedded in the GGUF, for models that ship a broken one
+  if let Some(path) = chat_template_path {
+    args.push("--chat-template-file".to_string());
+    args.push(path.to_string());
+  }
+
+  args.extend(launch.extra_args.iter().cloned());
+  args
+}
+
 /// Spawn the llama.cpp server as a sidecar process
 #[tauri::command]
 pub async fn spawn_llama_server(app_handle: AppHandle) -> Result<String, String> {
@@ -213,71 +361,77 @@ pub async fn spawn_llama_server(app_handle: AppHandle) -> Result<String, String>
   // Find an available port
   let port = find_available_port().await.map_err(|e| e.to_string())?;
 
+  start_server(app_handle, port, None).await
+}
+
+/// Launch the server on a known port, reusing `api_key` when given
+async fn start_server(
+  app_handle: AppHandle,
+  port: u16,
+  api_key: Option<String>,
+) -> Result<String, String> {
+  // Resolve launch flags, the model and an optional chat template override from user settings
+  let settings = crate::settings::service::load_user_settings(app_handle.clone())
+    .await
+    .map_err(|e| format!("Failed to load user settings: {}", e))?;
+  validate_launch_config(&settings.server_launch)?;
+
   // Create server configuration with the found port
-  let config = ServerConfig::new(&app_handle, port).map_err(|e| e.to_string())?;
+  let config = ServerConfig::new(
+    &app_handle,
+    port,
+    api_key,
+    settings.local_model_path.as_deref(),
+    settings.local_mmproj_path.as_deref(),
+  )
+  .map_err(|e| e.to_string())?;
+
+  let chat_template_path = settings.chat_template_path;
+  if let Some(path) = &chat_template_path {
+    if !std::path::Path::new(path).is_file() {
+      return Err(
+        ServerError::ConfigError(format!("Chat template file does not exist: {}", path)).into(),
+      );
+    }
+  }
 
   // Prepare sidecar command
   let shell = app_handle.shell();
   let sidecar_command = shell
     .sidecar("server")
     .map_err(|e| format!("Failed to get sidecar command: {}", e))?
-    .args([
-      "-m",
-      &config.text_model_path,
-      "-mm",
-      &config.mmproj_model_path,
-      "--port",
-      &config.port.to_string(),
-      "--api-key",
-      &config.api_key,
-      "--reasoning-format",
-      "none",
-      "-np", // Decode up to 3 sequences in parallel
-      "3",
-      "--ctx-size",
-      "32768",
-      "--n-predict",
-      "32768",
-      "--temp",
-      "0.7",
-      "--top-p",
-      "0.8",
-      "--top-k",
-      "20",
-      "--repeat-penalty",
-      "1.0",
-      "--presence-penalty",
-      "1.5",
-      "--seed",
-      "3407",
-      "-ctk", // Use q8 quant for kv cache
-      "q8_0",
-      "-ctv",
-      "q8_0",
-      "--mlock", // Keep model in RAM
-      "-fa",     // Use fast attention
-      "on",
-      "--no-webui",
-      "--log-disable",
-      "--offline",
-      "--jinja",
-    ]);
+    .args(build_server_args(
+      &config,
+      &settings.server_launch,
+      chat_template_path.as_deref(),
+    ));
 
   // Spawn the server process
-  let (mut _rx, child) = sidecar_command
+  let (rx, child) = sidecar_command
     .spawn()
     .map_err(|e| format!("Failed to spawn server process: {}", e))?;
+  let (exit_tx, mut exit_rx) = oneshot::channel();
+  tauri::async_runtime::spawn(watch_server_process(rx, exit_tx));
 
-  // Store the child process, port, and API key in global state
+  // Store the child process, port, API key and model in global state
   {
     let mut server_state = SERVER_STATE.lock().unwrap();
     server_state.child = Some(child);
     server_state.port = Some(config.port);
     server_state.api_key = Some(config.api_key.clone());
+    server_state.text_model_path = Some(config.text_model_path.clone());
+    server_state.mmproj_model_path = config.mmproj_model_path.clone();
   }
 
   // Wait for server to be ready
-  if let Err(e) = wait_for_server_ready(&config).await {
+  let ready = wait_for_server_ready(
+    || perform_health_check(&config),
+    &mut exit_rx,
+    MAX_HEALTH_CHECK_RETRIES,
+    HEALTH_CHECK_INTERVAL,
+  )
+  .await;
+  if let Err(e) = ready {
     // If server failed to start, clean up the process
     let _ = stop_llama_server().await;
     return Err(format!("Server failed to start: {}", e));
@@ -287,6 +441,105 @@ pub async fn spawn_llama_server(app_handle: AppHandle) -> Result<String, String>
   Ok(format!("Server started on port {}", config.port))
 }
 
+/// Restart the llama.cpp server, picking up any changed launch settings
+#[tauri::command]
+pub async fn restart_llama_server(app_handle: AppHandle) -> Result<String, String> {
+  // Check the new settings before taking down a working server
+  let settings = crate::settings::service::load_user_settings(app_handle.clone())
+    .await
+    .map_err(|e| format!("Failed to load user settings: {}", e))?;
+  validate_launch_config(&settings.server_launch)?;
+
+  if let Err(e) = stop_llama_server().await {
+    log::info!("[llama_server] Nothing to stop before restart: {}", e);
+  }
+  spawn_llama_server(app_handle).await
+}
+
+/// Serve a different GGUF model without restarting the app, with an optional vision
+/// projector for it. Passing no model goes back to the bundled one. The server comes back
+/// on the same port and API key; requests made meanwhile get `ModelSwitching`.
+#[tauri::command]
+pub async fn switch_model(
+  app_handle: AppHandle,
+  model_path: Option<String>,
+  mmproj_path: Option<String>,
+) -> Result<String, String> {
+  check_model_files(model_path.as_deref(), mmproj_path.as_deref())?;
+  if MODEL_SWITCHING
+    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+    .is_err()
+  {
+    return Err(ServerError::ModelSwitching.into());
+  }
+
+  // New requests now fail fast; let the ones already running finish on the old model
+  if !wait_for_generations(MODEL_SWITCH_DRAIN_TIMEOUT).await {
+    MODEL_SWITCHING.store(false, Ordering::SeqCst);
+    return Err(
+      ServerError::ProcessError(format!(
+        "Generations still running after {:?}, model not switched",
+        MODEL_SWITCH_DRAIN_TIMEOUT
+      ))
+      .into(),
+    );
+  }
+
+  let result = switch_model_inner(&app_handle, model_path.clone(), mmproj_path).await;
+  MODEL_SWITCHING.store(false, Ordering::SeqCst);
+
+  if result.is_ok() {
+    let event = ModelSwitchedEvent {
+      model_path,
+      timestamp: chrono::Utc::now().to_rfc3339(),
+    };
+    if let Err(e) = emit(MODEL_SWITCHED, event) {
+      log::warn!("[llama_server] Failed to emit model switched event: {}", e);
+    }
+  }
+  result
+}
+
+async fn switch_model_inner(
+  app_handle: &AppHandle,
+  model_path: Option<String>,
+  mmproj_path: Option<String>,
+) -> Result<String, String> {
+  log::info!("[llama_server] Switching local model");
+  let (port, api_key) = {
+    let server_state = SERVER_STATE.lock().unwrap();
+    (server_state.port, server_state.api_key.clone())
+  };
+
+  let mut settings = crate::settings::service::load_user_settings(app_handle.clone()).await?;
+  let previous_model = std::mem::replace(&mut settings.local_model_path, model_path);
+  let previous_mmproj = std::mem::replace(&mut settings.local_mmproj_path, mmproj_path);
+  crate::settings::service::save_user_settings(app_handle.clone(), settings.clone()).await?;
+
+  if let Err(e) = stop_llama_server().await {
+    log::info!("[llama_server] Nothing to stop before switching: {}", e);
+  }
+  let port = match port {
+    Some(port) => port,
+    None => find_available_port().await?,
+  };
+
+  match start_server(app_handle.clone(), port, api_key.clone()).await {
+    Ok(message) => Ok(message),
+    Err(e) => {
+      // Go back to the model that was working
+      log::error!("[llama_server] Failed to start switched model, reverting: {}", e);
+      settings.local_model_path = previous_model;
+      settings.local_mmproj_path = previous_mmproj;
+      crate::settings::service::save_user_settings(app_handle.clone(), settings).await?;
+      if let Err(revert_err) = start_server(app_handle.clone(), port, api_key).await {
+        log::error!("[llama_server] Failed to restart previous model: {}", revert_err);
+      }
+      Err(format!("Failed to switch model: {}", e))
+    }
+  }
+}
+
 pub async fn stop_llama_server() -> Result<String, String> {
   log::info!("[llama_server] Stopping llama.cpp server...");
 
@@ -298,9 +551,11 @@ pub async fn stop_llama_server() -> Result<String, String> {
         .kill()
         .map_err(|e| format!("Failed to kill server process: {}", e))?;
 
-      // Clear the port and API key as well
+      // Clear the port, API key and model as well
       server_state.port = None;
       server_state.api_key = None;
+      server_state.text_model_path = None;
+      server_state.mmproj_model_path = None;
 
       log::info!("[llama_server] Server stopped successfully");
       Ok("Server stopped successfully".to_string())
@@ -341,16 +596,274 @@ pub async fn perform_health_check(config: &ServerConfig) -> Result<Value, Server
   }
 }
 
-/// Wait for server to be ready (health check returns 200)
-async fn wait_for_server_ready(config: &ServerConfig) -> Result<(), ServerError> {
-  for attempt in 1..=MAX_HEALTH_CHECK_RETRIES {
+/// Report whether the server is running and healthy. With `detailed`, also include
+/// slot usage from `/slots` and throughput from `/metrics`, when the build serves them.
+#[tauri::command]
+pub async fn get_server_status(detailed: Option<bool>) -> Result<Value, String> {
+  let process_running = SERVER_STATE.lock().unwrap().child.is_some();
+  let config = match get_current_server_config() {
+    Ok(config) => config,
+    Err(ServerError::ModelSwitching) => {
+      return Ok(json!({ "status": "switching", "process_running": process_running }));
+    }
+    Err(_) => return Ok(json!({ "status": "stopped", "process_running": process_running })),
+  };
+
+  let mut status = match perform_health_check(&config).await {
+    Ok(health) => json!({ "status": health["status"], "health": health["response"] }),
+    Err(e) => json!({ "status": "unreachable", "error": e.to_string() }),
+  };
+  status["process_running"] = json!(process_running);
+  status["watchdog_restarts"] = json!(restart_count());
+  status["port"] = json!(config.port);
+  status["base_url"] = json!(config.base_url());
+
+  if detailed.unwrap_or(false) {
+    let slots = fetch_server_endpoint(&config, SLOTS_ENDPOINT)
+      .await
+      .and_then(|body| serde_json::from_str::<Value>(&body).ok())
+      .and_then(|body| count_slots(&body));
+    if let Some(slots) = slots {
+      status["slots"] = slots;
+    }
+
+    if let Some(body) = fetch_server_endpoint(&config, METRICS_ENDPOINT).await {
+      let metrics = parse_metrics(&body);
+      if metrics.as_object().is_some_and(|m| !m.is_empty()) {
+        status["metrics"] = metrics;
+      }
+    }
+  }
+
+  Ok(status)
+}
+
+/// GET an authenticated server endpoint, returning None if it is unavailable
+async fn fetch_server_endpoint(config: &ServerConfig, path: &str) -> Option<String> {
+  let response = reqwest::Client::new()
+    .get(format!("{}{}", config.base_url(), path))
+    .bearer_auth(&config.api_key)
+    .send()
+    .await
+    .ok()?;
+  if !response.status().is_success() {
+    log::debug!("[llama_server] {} returned {}", path, response.status());
+    return None;
+  }
+  response.text().await.ok()
+}
+
+/// Count idle and processing slots. Older builds report a numeric `state`
+/// instead of `is_processing`.
+fn count_slots(slots: &Value) -> Option<Value> {
+  let slots = slots.as_array()?;
+  let processing = slots
+    .iter()
+    .filter(|slot| {
+      slot["is_processing"]
+        .as_bool()
+        .unwrap_or_else(|| slot["state"].as_u64().unwrap_or(0) != 0)
+    })
+    .count();
+  Some(json!({ "idle": slots.len() - processing, "processing": processing }))
+}
+
+/// Pick the throughput and KV cache gauges out of the Prometheus text format
+fn parse_metrics(body: &str) -> Value {
+  let mut metrics = serde_json::Map::new();
+  for line in body.lines().filter(|line| !line.starts_with('#')) {
+    let mut parts = line.split_whitespace();
+    let (Some(name), Some(value)) = (parts.next(), parts.next()) else {
+      continue;
+    };
+    let key = match name {
+      "llamacpp:prompt_tokens_seconds" => "prompt_tokens_per_second",
+      "llamacpp:predicted_tokens_seconds" => "generation_tokens_per_second",
+      "llamacpp:kv_cache_usage_ratio" => "kv_cache_usage_ratio",
+      _ => continue,
+    };
+    if let Ok(value) = value.parse::<f64>() {
+      metrics.insert(key.to_string(), json!(value));
+    }
+  }
+  Value::Object(metrics)
+}
+
+/// Make sure the model is loaded before sending a request, briefly waiting
+/// out a "loading" status. Returns `ModelLoading` if it is still warming up.
+pub async fn ensure_model_ready(config: &ServerConfig) -> Result<(), ServerError> {
+  wait_while_loading(
+    || perform_health_check(config),
+    MODEL_LOADING_RETRIES,
+    MODEL_LOADING_INTERVAL,
+  )
+  .await
+}
+
+async fn wait_while_loading<F, Fut>(
+  mut check: F,
+  retries: u8,
+  interval: Duration,
+) -> Result<(), ServerError>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<Value, ServerError>>,
+{
+  for attempt in 1..=retries {
+    let response = check().await?;
+    match response.get("status").and_then(|s| s.as_str()) {
+      Some("healthy") => return Ok(()),
+      Some("loading") => {
+        log::info!(
+          "[llama_server] Model is loading (attempt {}/{}), waiting...",
+          attempt,
+          retries
+        );
+      }
+      _ => {
+        return Err(ServerError::NetworkError(
+          "Unexpected health check response".to_string(),
+        ))
+      }
+    }
+
+    if attempt < retries {
+      sleep(interval).await;
+    }
+  }
+
+  Err(ServerError::ModelLoading)
+}
+
+/// A failed request attempt, split by whether trying again could help
+#[derive(Debug)]
+pub enum RequestFailure {
+  Transient(String),
+  Fatal(String),
+}
+
+/// Send a completion request, retrying connection errors and 5xx responses with
+/// exponential backoff. Returns the response and the number of retries it took.
+pub async fn send_with_retry<F>(mut build_request: F) -> Result<(reqwest::Response, u8), String>
+where
+  F: FnMut() -> reqwest::RequestBuilder,
+{
+  retry_with_backoff(
+    || send_once(build_request()),
+    REQUEST_RETRY_ATTEMPTS,
+    REQUEST_RETRY_BASE_DELAY,
+  )
+  .await
+}
+
+async fn send_once(request: reqwest::RequestBuilder) -> Result<reqwest::Response, RequestFailure> {
+  let response = request
+    .send()
+    .await
+    .map_err(|e| RequestFailure::Transient(format!("Failed to send request: {}", e)))?;
+
+  let status = response.status();
+  if status.is_success() {
+    return Ok(response);
+  }
+  let error_text = response
+    .text()
+    .await
+    .unwrap_or_else(|_| "Unknown error".to_string());
+  let message = format!("Server returned error {}: {}", status, error_text);
+  if status.is_server_error() {
+    Err(RequestFailure::Transient(message))
+  } else {
+    Err(RequestFailure::Fatal(message))
+  }
+}
+
+async fn retry_with_backoff<T, F, Fut>(
+  mut attempt: F,
+  max_attempts: u8,
+  base_delay: Duration,
+) -> Result<(T, u8), String>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<T, RequestFailure>>,
+{
+  let mut delay = base_delay;
+  for retries in 0..max_attempts {
+    match attempt().await {
+      Ok(value) => return Ok((value, retries)),
+      Err(RequestFailure::Fatal(message)) => return Err(message),
+      Err(RequestFailure::Transient(message)) => {
+        if retries + 1 == max_attempts {
+          return Err(message);
+        }
+        log::warn!(
+          "[llama_server] Request failed (attempt {}/{}), retrying in {}ms: {}",
+          retries + 1,
+          max_attempts,
+          delay.as_millis(),
+          message
+        );
+        sleep(delay).await;
+        delay *= 2;
+      }
+    }
+  }
+
+  Err("Request was not attempted".to_string())
+}
+
+/// Follow the server's output and report on `exited` if the process stops. The last
+/// stderr line usually says why, e.g. a model file that failed to load.
+async fn watch_server_process(mut rx: Receiver<CommandEvent>, exited: oneshot::Sender<String>) {
+  let mut last_error = None;
+  while let Some(event) = rx.recv().await {
+    match event {
+      CommandEvent::Stderr(line) => {
+        let line = String::from_utf8_lossy(&line).trim().to_string();
+        if !line.is_empty() {
+          last_error = Some(line);
+        }
+      }
+      CommandEvent::Error(e) => last_error = Some(e),
+      CommandEvent::Terminated(payload) => {
+        let mut reason = match (payload.code, payload.signal) {
+          (Some(code), _) => format!("exit code {}", code),
+          (None, Some(signal)) => format!("signal {}", signal),
+          (None, None) => "unknown reason".to_string(),
+        };
+        if let Some(line) = last_error.take() {
+          reason = format!("{} ({})", reason, line);
+        }
+        log::warn!("[llama_server] Server process exited: {}", reason);
+        let _ = exited.send(reason);
+        return;
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Wait for server to be ready (health check returns 200). Connection errors and a
+/// "loading" status are waited out, but a process that exits fails straight away.
+async fn wait_for_server_ready<F, Fut>(
+  mut check: F,
+  exited: &mut oneshot::Receiver<String>,
+  retries: u8,
+  interval: Duration,
+) -> Result<(), ServerError>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<Value, ServerError>>,
+{
+  let mut watching = true;
+  for attempt in 1..=retries {
     log::debug!(
       "[llama_server] Health check attempt {}/{}",
       attempt,
-      MAX_HEALTH_CHECK_RETRIES
+      retries
     );
 
-    match perform_health_check(config).await {
+    match check().await {
       Ok(response) => {
         if let Some(status) = response.get("status") {
           if status == "healthy" {
@@ -362,12 +875,24 @@ async fn wait_for_server_ready(config: &ServerConfig) -> Result<(), ServerError>
         }
       }
       Err(e) => {
-        log::warn!("[llama_server] Health check failed: {}", e);
+        log::debug!("[llama_server] Health check failed: {}", e);
       }
     }
 
-    if attempt < MAX_HEALTH_CHECK_RETRIES {
-      sleep(HEALTH_CHECK_INTERVAL).await;
+    if attempt < retries {
+      tokio::select! {
+        _ = sleep(interval) => {}
+        result = &mut *exited, if watching => match result {
+          Ok(reason) => {
+            return Err(ServerError::ProcessError(format!(
+              "Server process exited during startup: {}",
+              reason
+            )));
+          }
+          // Output is no longer followed, keep polling until the timeout
+          Err(_) => watching = false,
+        }
+      }
     }
   }
 
@@ -375,3 +900,271 @@ async fn wait_for_server_ready(config: &ServerConfig) -> Result<(), ServerError>
     "Server failed to become healthy within timeout".to_string(),
   ))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test_config() -> ServerConfig {
+    ServerConfig {
+      port: 8080,
+      api_key: "session-test".to_string(),
+      text_model_path: "model.gguf".to_string(),
+      mmproj_model_path: Some("mmproj.gguf".to_string()),
+    }
+  }
+
+  #[test]
+  fn test_chat_template_arg_included_when_set() {
+    let args = build_server_args(
+      &test_config(),
+      &ServerLaunchConfig::default(),
+      Some("/tmp/template.jinja"),
+    );
+    let idx = args
+      .iter()
+      .position(|a| a == "--chat-template-file")
+      .expect("missing --chat-template-file");
+    assert_eq!(args[idx + 1], "/tmp/template.jinja");
+  }
+
+  #[test]
+  fn test_chat_template_arg_omitted_when_unset() {
+    let args = build_server_args(&test_config(), &ServerLaunchConfig::default(), None);
+    assert!(!args.iter().any(|a| a == "--chat-template-file"));
+    assert!(args.iter().any(|a| a == "--jinja"));
+  }
+
+  #[test]
+  fn test_switched_model_is_served_without_bundled_projector() {
+    let args = build_server_args(&test_config(), &ServerLaunchConfig::default(), None);
+    assert_eq!(args[args.iter().position(|a| a == "-mm").unwrap() + 1], "mmproj.gguf");
+
+    let config = ServerConfig {
+      text_model_path: "/models/other.gguf".to_string(),
+      mmproj_model_path: None,
+      ..test_config()
+    };
+    let args = build_server_args(&config, &ServerLaunchConfig::default(), None);
+    assert_eq!(args[args.iter().position(|a| a == "-m").unwrap() + 1], "/models/other.gguf");
+    assert!(!args.iter().any(|a| a == "-mm"));
+  }
+
+  #[test]
+  fn test_switch_target_files_are_checked() {
+    let root = std::env::temp_dir().join(format!("ambient-switch-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&root).unwrap();
+    let model = root.join("model.gguf");
+    let mmproj = root.join("mmproj.gguf");
+    std::fs::write(&model, b"gguf").unwrap();
+    std::fs::write(&mmproj, b"gguf").unwrap();
+    let model = model.to_str().unwrap();
+    let mmproj = mmproj.to_str().unwrap();
+
+    // Back to the bundled model
+    assert!(check_model_files(None, None).is_ok());
+    assert!(check_model_files(Some(model), None).is_ok());
+    assert!(check_model_files(Some(model), Some(mmproj)).is_ok());
+    assert!(matches!(
+      check_model_files(None, Some(mmproj)),
+      Err(ServerError::ConfigError(_))
+    ));
+    assert!(matches!(
+      check_model_files(Some(model), Some("/missing/mmproj.gguf")),
+      Err(ServerError::ModelNotFound(_))
+    ));
+
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_launch_config_builds_args() {
+    let launch = ServerLaunchConfig {
+      ctx_size: 65536,
+      parallel_sequences: 6,
+      kv_cache_quant: "f16".to_string(),
+      mlock: false,
+      flash_attention: false,
+      extra_args: vec!["--threads".to_string(), "8".to_string()],
+    };
+    let args = build_server_args(&test_config(), &launch, None);
+    let value_of = |flag: &str| {
+      let idx = args.iter().position(|a| a == flag).unwrap();
+      args[idx + 1].clone()
+    };
+    assert_eq!(value_of("--ctx-size"), "65536");
+    assert_eq!(value_of("-np"), "6");
+    assert_eq!(value_of("-ctk"), "f16");
+    assert_eq!(value_of("-fa"), "off");
+    assert!(!args.iter().any(|a| a == "--mlock"));
+    assert_eq!(&args[args.len() - 2..], ["--threads", "8"]);
+
+    // Defaults match the previous hardcoded flags
+    let args = build_server_args(&test_config(), &ServerLaunchConfig::default(), None);
+    assert!(args.iter().any(|a| a == "--mlock"));
+    assert_eq!(args[args.iter().position(|a| a == "--ctx-size").unwrap() + 1], "32768");
+  }
+
+  #[test]
+  fn test_slots_are_counted_across_builds() {
+    let slots = json!([
+      { "id": 0, "is_processing": true },
+      { "id": 1, "is_processing": false },
+      { "id": 2, "state": 1 },
+    ]);
+    assert_eq!(count_slots(&slots), Some(json!({ "idle": 1, "processing": 2 })));
+    assert_eq!(count_slots(&json!({ "error": "slots disabled" })), None);
+  }
+
+  #[test]
+  fn test_metrics_are_parsed_from_prometheus_text() {
+    let body = "# HELP llamacpp:prompt_tokens_seconds Average prompt throughput in tokens/s.\n\
+      # TYPE llamacpp:prompt_tokens_seconds gauge\n\
+      llamacpp:prompt_tokens_seconds 412.5\n\
+      llamacpp:predicted_tokens_seconds 38.25\n\
+      llamacpp:requests_processing 1\n";
+    assert_eq!(
+      parse_metrics(body),
+      json!({ "prompt_tokens_per_second": 412.5, "generation_tokens_per_second": 38.25 })
+    );
+    assert_eq!(parse_metrics(""), json!({}));
+  }
+
+  #[test]
+  fn test_invalid_launch_config_is_rejected() {
+    assert!(validate_launch_config(&ServerLaunchConfig::default()).is_ok());
+
+    let small_ctx = ServerLaunchConfig {
+      ctx_size: 256,
+      ..Default::default()
+    };
+    assert!(matches!(
+      validate_launch_config(&small_ctx),
+      Err(ServerError::ConfigError(_))
+    ));
+
+    let bad_quant = ServerLaunchConfig {
+      kv_cache_quant: "q3_fast".to_string(),
+      ..Default::default()
+    };
+    assert!(validate_launch_config(&bad_quant).is_err());
+
+    let no_sequences = ServerLaunchConfig {
+      parallel_sequences: 0,
+      ..Default::default()
+    };
+    assert!(validate_launch_config(&no_sequences).is_err());
+
+    for extra_args in [vec!["--port", "9000"], vec!["--api-key=abc"], vec!["--host", "0.0.0.0"]] {
+      let overrides = ServerLaunchConfig {
+        extra_args: extra_args.into_iter().map(String::from).collect(),
+        ..Default::default()
+      };
+      assert!(validate_launch_config(&overrides).is_err());
+    }
+    let threads = ServerLaunchConfig {
+      extra_args: vec!["--threads".to_string(), "8".to_string()],
+      ..Default::default()
+    };
+    assert!(validate_launch_config(&threads).is_ok());
+  }
+
+  #[test]
+  fn test_wait_while_loading_retries_until_healthy() {
+    let mut statuses = vec!["healthy", "loading"];
+    let mut calls = 0;
+    let result = tauri::async_runtime::block_on(wait_while_loading(
+      || {
+        calls += 1;
+        let status = statuses.pop().unwrap();
+        async move { Ok(json!({ "status": status })) }
+      },
+      3,
+      Duration::from_millis(1),
+    ));
+    assert!(result.is_ok());
+    assert_eq!(calls, 2);
+  }
+
+  #[test]
+  fn test_wait_while_loading_gives_up_with_model_loading() {
+    let result = tauri::async_runtime::block_on(wait_while_loading(
+      || async { Ok(json!({ "status": "loading" })) },
+      2,
+      Duration::from_millis(1),
+    ));
+    assert!(matches!(result, Err(ServerError::ModelLoading)));
+  }
+
+  #[test]
+  fn test_transient_failures_are_retried_with_backoff() {
+    let mut calls = 0;
+    let result = tauri::async_runtime::block_on(retry_with_backoff(
+      || {
+        calls += 1;
+        let outcome = if calls < 3 {
+          Err(RequestFailure::Transient("503".to_string()))
+        } else {
+          Ok("done")
+        };
+        async move { outcome }
+      },
+      3,
+      Duration::from_millis(1),
+    ));
+    assert_eq!(result, Ok(("done", 2)));
+
+    let result: Result<((), u8), String> = tauri::async_runtime::block_on(retry_with_backoff(
+      || async { Err(RequestFailure::Transient("connection refused".to_string())) },
+      3,
+      Duration::from_millis(1),
+    ));
+    assert_eq!(result, Err("connection refused".to_string()));
+  }
+
+  #[test]
+  fn test_client_errors_are_not_retried() {
+    let mut calls = 0;
+    let result: Result<((), u8), String> = tauri::async_runtime::block_on(retry_with_backoff(
+      || {
+        calls += 1;
+        async { Err(RequestFailure::Fatal("400".to_string())) }
+      },
+      3,
+      Duration::from_millis(1),
+    ));
+    assert_eq!(result, Err("400".to_string()));
+    assert_eq!(calls, 1);
+  }
+
+  #[test]
+  fn test_startup_waits_while_loading_but_not_for_an_exited_process() {
+    let mut calls = 0;
+    let (_exit_tx, mut exit_rx) = oneshot::channel::<String>();
+    let result = tauri::async_runtime::block_on(wait_for_server_ready(
+      || {
+        calls += 1;
+        let status = if calls < 3 { "loading" } else { "healthy" };
+        async move { Ok(json!({ "status": status })) }
+      },
+      &mut exit_rx,
+      5,
+      Duration::from_millis(1),
+    ));
+    assert!(result.is_ok());
+    assert_eq!(calls, 3);
+
+    let (exit_tx, mut exit_rx) = oneshot::channel();
+    exit_tx.send("exit code 1 (failed to load model)".to_string()).unwrap();
+    let result = tauri::async_runtime::block_on(wait_for_server_ready(
+      || async { Err(ServerError::NetworkError("connection refused".to_string())) },
+      &mut exit_rx,
+      100,
+      Duration::from_secs(60),
+    ));
+    match result {
+      Err(ServerError::ProcessError(message)) => assert!(message.contains("failed to load model")),
+      other => panic!("expected the exit to be reported, got {:?}", other),
+    }
+  }
+}
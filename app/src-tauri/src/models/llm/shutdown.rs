@@ -0,0 +1,95 @@
+use crate::models::llm::server::stop_llama_server;
+use crate::models::llm::types::LlmError;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Number of generations currently running
+static ACTIVE_GENERATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set once the app starts quitting. Streams stop early and new generations are refused.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Counts a generation as active until dropped
+pub struct GenerationGuard;
+
+impl Drop for GenerationGuard {
+  fn drop(&mut self) {
+    ACTIVE_GENERATIONS.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+/// Register a generation so shutdown waits for it to save its response
+pub fn begin_generation() -> Result<GenerationGuard, LlmError> {
+  if is_shutting_down() {
+    return Err(LlmError::ShuttingDown);
+  }
+  ACTIVE_GENERATIONS.fetch_add(1, Ordering::SeqCst);
+  Ok(GenerationGuard)
+}
+
+/// Whether streaming providers should stop reading and save what they have
+pub fn is_shutting_down() -> bool {
+  SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Ask running generations to finish, wait up to `grace` for them to save, then
+/// stop the llama server
+pub async fn shutdown(grace: Duration) {
+  SHUTTING_DOWN.store(true, Ordering::SeqCst);
+
+  if !wait_for_idle(&ACTIVE_GENERATIONS, grace, DRAIN_POLL_INTERVAL).await {
+    log::warn!(
+      "[shutdown] {} generation(s) still running after {:?}, stopping anyway",
+      ACTIVE_GENERATIONS.load(Ordering::SeqCst),
+      grace
+    );
+  }
+
+  match stop_llama_server().await {
+    Ok(_) => log::info!("[shutdown] Llama server stopped"),
+    Err(e) => log::warn!("[shutdown] Failed to stop llama server: {}", e),
+  }
+}
+
+/// Wait up to `grace` for running generations to finish. Returns false if some are still
+/// running.
+pub async fn wait_for_generations(grace: Duration) -> bool {
+  wait_for_idle(&ACTIVE_GENERATIONS, grace, DRAIN_POLL_INTERVAL).await
+}
+
+/// Poll until `active` reaches zero. Returns false if `grace` ran out first.
+async fn wait_for_idle(active: &AtomicUsize, grace: Duration, poll: Duration) -> bool {
+  let started = Instant::now();
+  while active.load(Ordering::SeqCst) > 0 {
+    if started.elapsed() >= grace {
+      return false;
+    }
+    sleep(poll).await;
+  }
+  true
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Arc;
+
+  #[test]
+  fn test_wait_for_idle_waits_for_generations_within_grace() {
+    tauri::async_runtime::block_on(async {
+      let active = Arc::new(AtomicUsize::new(1));
+      let finishing = active.clone();
+      tauri::async_runtime::spawn(async move {
+        sleep(Duration::from_millis(20)).await;
+        finishing.fetch_sub(1, Ordering::SeqCst);
+      });
+      assert!(wait_for_idle(&active, Duration::from_secs(1), Duration::from_millis(5)).await);
+
+      let stuck = AtomicUsize::new(1);
+      assert!(!wait_for_idle(&stuck, Duration::from_millis(20), Duration::from_millis(5)).await);
+    });
+  }
+}
@@ -1,11 +1,85 @@
+use crate::models::llm::server::ServerError;
+use crate::settings::types::ModelSelection;
 use tauri::AppHandle;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 /// Policy for choosing which provider to use
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProviderPolicy {
   Default,
   ForceLocal,
+  /// Use a cloud model even if the user picked a local one
+  ForceCloud,
+}
+
+/// Why a generation failed. The local model's transient states get their own variants
+/// so callers can ask the user to wait instead of reporting a failure.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LlmError {
+  ModelLoading,
+  ModelSwitching,
+  /// The request itself is malformed, so every provider would reject it
+  InvalidRequest(String),
+  ShuttingDown,
+  /// Failed after part of the response was streamed or saved
+  Interrupted(String),
+  Other(String),
+}
+
+impl LlmError {
+  /// Whether another provider might succeed where this one failed, e.g. a server that
+  /// is down, an expired sign-in or a rate limit
+  pub fn is_retryable(&self) -> bool {
+    match self {
+      LlmError::ModelLoading | LlmError::ModelSwitching | LlmError::Other(_) => true,
+      LlmError::InvalidRequest(_) | LlmError::ShuttingDown | LlmError::Interrupted(_) => false,
+    }
+  }
+}
+
+impl std::fmt::Display for LlmError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LlmError::ModelLoading => write!(f, "{}", ServerError::ModelLoading),
+      LlmError::ModelSwitching => write!(f, "{}", ServerError::ModelSwitching),
+      LlmError::ShuttingDown => write!(f, "Ambient is shutting down"),
+      LlmError::InvalidRequest(msg) | LlmError::Interrupted(msg) | LlmError::Other(msg) => {
+        write!(f, "{}", msg)
+      }
+    }
+  }
+}
+
+impl std::error::Error for LlmError {}
+
+impl From<String> for LlmError {
+  fn from(error: String) -> Self {
+    LlmError::Other(error)
+  }
+}
+
+impl From<&str> for LlmError {
+  fn from(error: &str) -> Self {
+    LlmError::Other(error.to_string())
+  }
+}
+
+impl From<ServerError> for LlmError {
+  fn from(error: ServerError) -> Self {
+    match error {
+      ServerError::ModelLoading => LlmError::ModelLoading,
+      ServerError::ModelSwitching => LlmError::ModelSwitching,
+      other => LlmError::Other(other.to_string()),
+    }
+  }
+}
+
+/// Convert LlmError to String for Tauri commands
+impl From<LlmError> for String {
+  fn from(error: LlmError) -> Self {
+    error.to_string()
+  }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -17,6 +91,72 @@ pub struct LlmRequest {
   pub use_thinking: Option<bool>,
   pub stream: Option<bool>,
   pub current_message_id: Option<String>,
+  /// Overrides the model from user settings for cloud requests
+  pub model: Option<ModelSelection>,
+  /// Message row that a streaming response is saved into as it arrives
+  pub response_message_id: Option<String>,
+  #[serde(default)]
+  pub sampling: SamplingParams,
+  /// Messages before the current one are covered by a summary in the system prompt,
+  /// so providers leave them out
+  #[serde(default)]
+  pub history_summarized: bool,
+}
+
+/// Sampling overrides for a request. Unset fields keep the provider's defaults.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct SamplingParams {
+  pub temperature: Option<f32>,
+  pub top_p: Option<f32>,
+  pub top_k: Option<u32>,
+  pub repeat_penalty: Option<f32>,
+  /// Generation halts when any of these is produced
+  pub stop: Option<Vec<String>>,
+}
+
+impl SamplingParams {
+  pub fn validate(&self) -> Result<(), String> {
+    if let Some(temperature) = self.temperature {
+      if !temperature.is_finite() || temperature < 0.0 {
+        return Err(format!("Temperature must be 0 or more, got {}", temperature));
+      }
+    }
+    if let Some(top_p) = self.top_p {
+      if !(0.0..=1.0).contains(&top_p) {
+        return Err(format!("top_p must be between 0 and 1, got {}", top_p));
+      }
+    }
+    if let Some(repeat_penalty) = self.repeat_penalty {
+      if !repeat_penalty.is_finite() || repeat_penalty <= 0.0 {
+        return Err(format!("Repeat penalty must be above 0, got {}", repeat_penalty));
+      }
+    }
+    if self.stop.iter().flatten().any(|s| s.is_empty()) {
+      return Err("Stop sequences can't be empty".to_string());
+    }
+    Ok(())
+  }
+
+  /// Validate and write the set parameters into an OpenAI-style completions body
+  pub fn apply(&self, body: &mut Value) -> Result<(), LlmError> {
+    self.validate().map_err(LlmError::InvalidRequest)?;
+    if let Some(temperature) = self.temperature {
+      body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = self.top_p {
+      body["top_p"] = json!(top_p);
+    }
+    if let Some(top_k) = self.top_k {
+      body["top_k"] = json!(top_k);
+    }
+    if let Some(repeat_penalty) = self.repeat_penalty {
+      body["repeat_penalty"] = json!(repeat_penalty);
+    }
+    if let Some(stop) = &self.stop {
+      body["stop"] = json!(stop);
+    }
+    Ok(())
+  }
 }
 
 impl LlmRequest {
@@ -56,6 +196,34 @@ impl LlmRequest {
     self.current_message_id = current_message_id;
     self
   }
+
+  pub fn with_model(mut self, model: Option<ModelSelection>) -> Self {
+    self.model = model;
+    self
+  }
+
+  pub fn with_response_message_id(mut self, response_message_id: Option<String>) -> Self {
+    self.response_message_id = response_message_id;
+    self
+  }
+
+  pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+    self.sampling = sampling;
+    self
+  }
+
+  pub fn with_history_summarized(mut self, history_summarized: bool) -> Self {
+    self.history_summarized = history_summarized;
+    self
+  }
+}
+
+/// Text a provider generated and the tokens it used
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LlmResponse {
+  pub text: String,
+  pub prompt_tokens: u64,
+  pub completion_tokens: u64,
 }
 
 /// Common interface for LLM providers
@@ -65,5 +233,44 @@ pub trait LlmProvider: Send + Sync {
     &self,
     app_handle: AppHandle,
     request: LlmRequest,
-  ) -> Result<String, String>;
+  ) -> Result<LlmResponse, LlmError>;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_sampling_overrides_only_set_fields_and_reject_bad_values() {
+    let mut body = json!({ "temperature": 0.7, "top_k": 20 });
+    let sampling = SamplingParams {
+      temperature: Some(0.0),
+      stop: Some(vec!["</step>".to_string()]),
+      ..Default::default()
+    };
+    sampling.apply(&mut body).unwrap();
+    assert_eq!(body, json!({ "temperature": 0.0, "top_k": 20, "stop": ["</step>"] }));
+
+    let bad = |sampling: SamplingParams| sampling.validate().is_err();
+    assert!(bad(SamplingParams { temperature: Some(-0.1), ..Default::default() }));
+    assert!(bad(SamplingParams { top_p: Some(1.5), ..Default::default() }));
+    assert!(bad(SamplingParams { repeat_penalty: Some(0.0), ..Default::default() }));
+    assert!(bad(SamplingParams { stop: Some(vec![String::new()]), ..Default::default() }));
+
+    // Another provider would reject it too
+    let out_of_range = SamplingParams { top_p: Some(1.5), ..Default::default() };
+    let err = out_of_range.apply(&mut body).unwrap_err();
+    assert!(!err.is_retryable());
+  }
+
+  #[test]
+  fn test_server_model_states_stay_typed() {
+    assert_eq!(LlmError::from(ServerError::ModelLoading), LlmError::ModelLoading);
+    assert_eq!(LlmError::from(ServerError::ModelSwitching), LlmError::ModelSwitching);
+    assert_eq!(
+      LlmError::from(ServerError::ServerNotRunning),
+      LlmError::Other("Server is not running".to_string())
+    );
+    assert_eq!(String::from(LlmError::ModelLoading), ServerError::ModelLoading.to_string());
+  }
 }
@@ -0,0 +1,166 @@
+use crate::constants::{
+  WATCHDOG_FAILURE_THRESHOLD, WATCHDOG_INTERVAL, WATCHDOG_MAX_RESTART_ATTEMPTS,
+  WATCHDOG_RESTART_BASE_DELAY,
+};
+use crate::events::{emitter::emit, types::{ServerRestartedEvent, SERVER_RESTARTED}};
+use crate::models::llm::server::{get_current_server_config, perform_health_check, restart_llama_server};
+use crate::models::llm::shutdown::is_shutting_down;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::time::sleep;
+
+/// Number of times the watchdog has brought the server back since launch
+static RESTART_COUNT: AtomicU32 = AtomicU32::new(0);
+
+pub fn restart_count() -> u32 {
+  RESTART_COUNT.load(Ordering::SeqCst)
+}
+
+#[derive(Debug, PartialEq)]
+enum WatchdogAction {
+  Wait,
+  Restart(Duration),
+  GiveUp,
+}
+
+/// Tracks consecutive failures so a server that keeps crashing isn't restarted forever
+#[derive(Debug, Default)]
+struct WatchdogState {
+  failed_checks: u8,
+  failed_restarts: u8,
+}
+
+impl WatchdogState {
+  fn after_health_check(&mut self, healthy: bool) -> WatchdogAction {
+    if healthy {
+      self.failed_checks = 0;
+      return WatchdogAction::Wait;
+    }
+    self.failed_checks = self.failed_checks.saturating_add(1);
+    if self.failed_checks < WATCHDOG_FAILURE_THRESHOLD {
+      return WatchdogAction::Wait;
+    }
+    self.next_restart()
+  }
+
+  fn after_restart(&mut self, succeeded: bool) -> WatchdogAction {
+    if succeeded {
+      *self = WatchdogState::default();
+      return WatchdogAction::Wait;
+    }
+    self.failed_restarts += 1;
+    self.next_restart()
+  }
+
+  /// Back off exponentially between attempts, then stop trying
+  fn next_restart(&self) -> WatchdogAction {
+    if self.failed_restarts >= WATCHDOG_MAX_RESTART_ATTEMPTS {
+      return WatchdogAction::GiveUp;
+    }
+    WatchdogAction::Restart(WATCHDOG_RESTART_BASE_DELAY * 2u32.pow(self.failed_restarts as u32))
+  }
+}
+
+/// Periodically check the llama server and restart it if it stops answering. Only a
+/// server that was started and not stopped on purpose is watched.
+pub fn spawn_watchdog(app_handle: AppHandle) {
+  tauri::async_runtime::spawn(async move {
+    let mut state = WatchdogState::default();
+    loop {
+      sleep(WATCHDOG_INTERVAL).await;
+      if is_shutting_down() {
+        return;
+      }
+
+      // Stopped or switching models, nothing to watch
+      let Ok(config) = get_current_server_config() else {
+        state = WatchdogState::default();
+        continue;
+      };
+
+      let healthy = match perform_health_check(&config).await {
+        Ok(_) => true,
+        Err(e) => {
+          log::warn!("[watchdog] Llama server health check failed: {}", e);
+          false
+        }
+      };
+
+      let mut action = state.after_health_check(healthy);
+      while let WatchdogAction::Restart(delay) = action {
+        // Restarting can't help if the model is gone
+        if !std::path::Path::new(&config.text_model_path).is_file() {
+          log::error!(
+            "[watchdog] Model file is missing, not restarting: {}",
+            config.text_model_path
+          );
+          return;
+        }
+
+        log::warn!("[watchdog] Restarting llama server in {}s", delay.as_secs());
+        sleep(delay).await;
+        if is_shutting_down() {
+          return;
+        }
+
+        let result = restart_llama_server(app_handle.clone()).await;
+        match &result {
+          Ok(_) => {
+            let restart_count = RESTART_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+            log::info!("[watchdog] Llama server restarted ({} so far)", restart_count);
+            let event = ServerRestartedEvent {
+              restart_count,
+              timestamp: chrono::Utc::now().to_rfc3339(),
+            };
+            if let Err(e) = emit(SERVER_RESTARTED, event) {
+              log::warn!("[watchdog] Failed to emit server restarted event: {}", e);
+            }
+          }
+          Err(e) => log::error!("[watchdog] Failed to restart llama server: {}", e),
+        }
+        action = state.after_restart(result.is_ok());
+      }
+
+      if action == WatchdogAction::GiveUp {
+        log::error!(
+          "[watchdog] Giving up after {} failed restarts",
+          WATCHDOG_MAX_RESTART_ATTEMPTS
+        );
+        return;
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_restarts_back_off_then_give_up() {
+    let mut state = WatchdogState::default();
+    assert_eq!(state.after_health_check(false), WatchdogAction::Wait);
+    assert_eq!(state.after_health_check(true), WatchdogAction::Wait);
+    for _ in 1..WATCHDOG_FAILURE_THRESHOLD {
+      assert_eq!(state.after_health_check(false), WatchdogAction::Wait);
+    }
+    assert_eq!(
+      state.after_health_check(false),
+      WatchdogAction::Restart(WATCHDOG_RESTART_BASE_DELAY)
+    );
+    assert_eq!(
+      state.after_restart(false),
+      WatchdogAction::Restart(WATCHDOG_RESTART_BASE_DELAY * 2)
+    );
+    assert_eq!(state.after_restart(true), WatchdogAction::Wait);
+    assert_eq!(state.failed_checks, 0);
+
+    state.failed_checks = WATCHDOG_FAILURE_THRESHOLD;
+    let mut action = state.next_restart();
+    while let WatchdogAction::Restart(_) = action {
+      action = state.after_restart(false);
+    }
+    assert_eq!(action, WatchdogAction::GiveUp);
+  }
+}
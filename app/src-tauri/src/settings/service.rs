@@ -54,7 +54,12 @@ pub async fn save_user_settings(
   app_handle: AppHandle,
   settings: UserSettings,
 ) -> Result<(), String> {
-  save_settings_internal(&app_handle, &settings).await
+  save_settings_internal(&app_handle, &settings).await?;
+  // Turning debug logging off also removes what it recorded
+  if !settings.llm_debug_logging {
+    crate::db::llm_debug::clear_llm_exchanges(&app_handle)?;
+  }
+  Ok(())
 }
 
 #[tauri::command]
@@ -80,12 +80,13 @@ pub enum HudState {
 }
 
 // Model selection
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "settings.ts")]
 pub enum ModelSelection {
   Local,
   Fast,
   Pro,
+  Ollama,
 }
 
 impl Default for ModelSelection {
@@ -100,6 +101,7 @@ impl ModelSelection {
       Self::Local => "local",
       Self::Fast => "fast",
       Self::Pro => "pro",
+      Self::Ollama => "ollama",
     }
   }
 
@@ -108,16 +110,103 @@ impl ModelSelection {
       "local" => Self::Local,
       "fast" => Self::Fast,
       "pro" => Self::Pro,
+      "ollama" => Self::Ollama,
       _ => Self::Local, // Default fallback
     }
   }
 }
 
+/// Launch flags for the local llama.cpp server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "settings.ts")]
+#[serde(default)]
+pub struct ServerLaunchConfig {
+  pub ctx_size: u32,
+  pub parallel_sequences: u32,
+  /// Quantization for the KV cache, e.g. "q8_0" or "f16"
+  pub kv_cache_quant: String,
+  pub mlock: bool,
+  pub flash_attention: bool,
+  /// Passed to the server as-is, after the built-in flags. Flags Ambient manages,
+  /// such as --port and --api-key, are rejected.
+  pub extra_args: Vec<String>,
+}
+
+impl Default for ServerLaunchConfig {
+  fn default() -> Self {
+    Self {
+      ctx_size: 32768,
+      parallel_sequences: 3,
+      kv_cache_quant: "q8_0".to_string(),
+      mlock: true,
+      flash_attention: true,
+      extra_args: Vec::new(),
+    }
+  }
+}
+
+/// Price of a cloud model in USD per million tokens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "settings.ts")]
+pub struct ModelPrice {
+  pub model: String,
+  pub prompt_per_million: f64,
+  pub completion_per_million: f64,
+}
+
+pub fn default_model_prices() -> Vec<ModelPrice> {
+  let price = |model: &str, prompt_per_million, completion_per_million| ModelPrice {
+    model: model.to_string(),
+    prompt_per_million,
+    completion_per_million,
+  };
+  vec![
+    price("fast", 0.5, 3.0),
+    price("pro", 2.0, 12.0),
+    price("computer-use", 1.25, 10.0),
+  ]
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "settings.ts")]
 pub struct UserSettings {
   pub hud_size: HudSizeOption,
   pub model_selection: ModelSelection,
+  /// Overrides the local model's embedded chat template when set
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[ts(optional)]
+  pub chat_template_path: Option<String>,
+  /// Persist raw provider requests and responses for debugging
+  #[serde(default)]
+  pub llm_debug_logging: bool,
+  /// Base URL of the Ollama server, defaults to localhost
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[ts(optional)]
+  pub ollama_base_url: Option<String>,
+  /// Ollama model name to generate with
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[ts(optional)]
+  pub ollama_model: Option<String>,
+  /// GGUF model served instead of the bundled one, set by switching models
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[ts(optional)]
+  pub local_model_path: Option<String>,
+  /// Vision projector for `local_model_path`. Without one the switched model is text-only.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[ts(optional)]
+  pub local_mmproj_path: Option<String>,
+  /// Takes effect the next time the local server starts
+  #[serde(default)]
+  pub server_launch: ServerLaunchConfig,
+  /// Let computer use act without asking before each action
+  #[serde(default)]
+  pub computer_use_autonomous: bool,
+  /// Used to estimate the cost of cloud usage per conversation
+  #[serde(default = "default_model_prices")]
+  pub model_prices: Vec<ModelPrice>,
+  /// Models to try in order when the selected one fails. Empty turns failover off.
+  #[serde(default)]
+  pub provider_fallbacks: Vec<ModelSelection>,
 }
 
 impl Default for UserSettings {
@@ -125,6 +214,16 @@ impl Default for UserSettings {
     Self {
       hud_size: HudSizeOption::default(),
       model_selection: ModelSelection::default(),
+      chat_template_path: None,
+      llm_debug_logging: false,
+      ollama_base_url: None,
+      ollama_model: None,
+      local_model_path: None,
+      local_mmproj_path: None,
+      server_launch: ServerLaunchConfig::default(),
+      computer_use_autonomous: false,
+      model_prices: default_model_prices(),
+      provider_fallbacks: Vec::new(),
     }
   }
 }
@@ -8,13 +8,18 @@ use crate::events::{
   types::{DOWNLOAD_INFORMATION, DownloadInformationEvent, DOWNLOAD_STARTED, DownloadStartedEvent, DOWNLOAD_PROGRESS, DownloadProgressEvent, DOWNLOAD_FINISHED, DownloadFinishedEvent},
 };
 use crate::models::llm::server::spawn_llama_server;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::{fs::File, io::Write};
+use std::time::Instant;
+use std::{fs::File, fs::OpenOptions, io::Read, io::Write};
 use tauri::{AppHandle, Emitter, Manager};
 use tokio_stream::StreamExt;
+use ts_rs::TS;
 
 /// Global lock to prevent multiple concurrent setup processes
 static SETUP_RUNNING: AtomicBool = AtomicBool::new(false);
@@ -40,6 +45,8 @@ impl DownloadItem {
     }
   }
 
+  /// Download into a `.part` file next to the target, resuming from whatever an earlier
+  /// attempt left behind. The file is only moved into place once it is complete.
   pub async fn download(&self) -> Result<(), String> {
     let parent_dir = self.out_file
       .parent()
@@ -55,15 +62,61 @@ impl DownloadItem {
       }
       return Ok(());
     }
-      
+
+    let part_file = partial_path(&self.out_file);
+    let validator_file = validator_path(&self.out_file);
+    // A partial file without the validator it was downloaded under can't be checked
+    // against the server's copy, so it isn't resumed
+    let saved_validator = fs::read_to_string(&validator_file).ok();
+    let partial_len = match saved_validator {
+      Some(_) => fs::metadata(&part_file).map(|m| m.len()).unwrap_or(0),
+      None => 0,
+    };
     let client = Client::new();
-    let response = client.get(self.url).send().await.map_err(|e| e.to_string())?;
+    let mut response = self.request(&client, partial_len, saved_validator.as_deref()).await?;
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+      // The range starts at the end of the file when an earlier attempt got every byte
+      // but stopped before moving the file into place. A 416 isn't conditional on
+      // `If-Range`, so the file must also still be the one the part was taken from.
+      let total = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(content_range_total);
+      if total == Some(partial_len) && resume_validator(response.headers()) == saved_validator {
+        log::info!("[setup] Partial download of model {} is already complete", self.id);
+        return self.finish(&part_file);
+      }
+      log::warn!("[setup] Partial download of model {} is unusable, starting over", self.id);
+      response = self.request(&client, 0, None).await?;
+    }
+    let response = response
+      .error_for_status()
+      .map_err(|e| format!("Download of model {} failed: {}", self.id, e))?;
+
+    // A server whose file changed since the partial download answers the `If-Range`
+    // request with the whole new file
+    let offset = resume_offset(response.status(), partial_len);
+    let total = response.content_length().map(|len| offset + len);
+    if offset == 0 {
+      match resume_validator(response.headers()) {
+        Some(validator) => fs::write(&validator_file, validator)
+          .map_err(|e| format!("Failed to save download state for model {}: {}", self.id, e))?,
+        None => {
+          let _ = fs::remove_file(&validator_file);
+        }
+      }
+    }
 
     // Send start update
-    log::info!(
-      "[setup] Downloading model {}",
-      self.id,
-    );
+    if offset > 0 {
+      log::info!("[setup] Resuming model {} from byte {}", self.id, offset);
+    } else {
+      log::info!(
+        "[setup] Downloading model {}",
+        self.id,
+      );
+    }
 
     if let Err(e) = emit(
       DOWNLOAD_STARTED,
@@ -72,28 +125,74 @@ impl DownloadItem {
       log::error!("Failed to emit event: {}", e);
     }
 
-    let mut file = File::create(&self.out_file)
+    let mut options = OpenOptions::new();
+    if offset > 0 {
+      options.append(true);
+    } else {
+      options.write(true).create(true).truncate(true);
+    }
+    let mut file = options
+      .open(&part_file)
       .map_err(|e| format!("Failed to create file for model {}: {}", self.id, e))?;
-    let mut downloaded: u64 = 0;
+    let mut downloaded: u64 = offset;
+    let started = Instant::now();
     let mut stream = response.bytes_stream();
 
-    // Process the stream of chunks
+    // Process the stream of chunks. On a network error the partial file stays so a
+    // retry can pick up where this one stopped.
     while let Some(chunk) = stream.next().await {
       let chunk_data = chunk.map_err(|e| e.to_string())?;
       file.write_all(&chunk_data).map_err(|e| e.to_string())?;
       downloaded += chunk_data.len() as u64;
 
+      let elapsed = started.elapsed().as_secs_f64();
+      let bytes_per_second = if elapsed > 0.0 {
+        ((downloaded - offset) as f64 / elapsed) as u64
+      } else {
+        0
+      };
+
       // Send progress update
       if let Err(e) = emit(
         DOWNLOAD_PROGRESS,
         DownloadProgressEvent {
           id: self.id,
           total_progress: downloaded,
+          total,
+          bytes_per_second,
         },
       ) {
         log::error!("Failed to emit progress event: {}", e);
       }
     }
+    file.flush().map_err(|e| e.to_string())?;
+    drop(file);
+
+    if let Some(total) = total {
+      if downloaded < total {
+        return Err(format!(
+          "Download of model {} ended early at {} of {} bytes",
+          self.id, downloaded, total
+        ));
+      }
+      if downloaded > total {
+        // Appended to the wrong data, nothing worth resuming from
+        let _ = fs::remove_file(&part_file);
+        return Err(format!(
+          "Download of model {} is larger than expected ({} of {} bytes)",
+          self.id, downloaded, total
+        ));
+      }
+    }
+    self.finish(&part_file)
+  }
+
+  /// Move a complete download into place
+  fn finish(&self, part_file: &Path) -> Result<(), String> {
+    fs::rename(part_file, &self.out_file)
+      .map_err(|e| format!("Failed to move model {} into place: {}", self.id, e))?;
+    let _ = fs::remove_file(validator_path(&self.out_file));
+
     // Send completion update
     if let Err(e) = emit(
       DOWNLOAD_FINISHED,
@@ -103,6 +202,70 @@ impl DownloadItem {
     }
     Ok(())
   }
+
+  /// Request the file, asking only for the bytes after `offset` when there are some.
+  /// With a `validator`, the server sends the whole file instead if it has changed.
+  async fn request(
+    &self,
+    client: &Client,
+    offset: u64,
+    validator: Option<&str>,
+  ) -> Result<reqwest::Response, String> {
+    let mut request = client.get(self.url);
+    if offset > 0 {
+      request = request.header(RANGE, format!("bytes={}-", offset));
+      if let Some(validator) = validator {
+        request = request.header(IF_RANGE, validator);
+      }
+    }
+    request.send().await.map_err(|e| e.to_string())
+  }
+}
+
+/// Where an in-progress download is written before it is complete
+fn partial_path(out_file: &Path) -> PathBuf {
+  let mut path = out_file.as_os_str().to_owned();
+  path.push(".part");
+  PathBuf::from(path)
+}
+
+/// Where the validator of the response a partial download came from is kept
+fn validator_path(out_file: &Path) -> PathBuf {
+  let mut path = out_file.as_os_str().to_owned();
+  path.push(".part.validator");
+  PathBuf::from(path)
+}
+
+/// Value for `If-Range` that identifies this version of the file: a strong `ETag`, or
+/// `Last-Modified` when there is none. Weak ETags aren't allowed in `If-Range`.
+fn resume_validator(headers: &HeaderMap) -> Option<String> {
+  let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+  header(ETAG)
+    .filter(|etag| !etag.starts_with("W/"))
+    .or_else(|| header(LAST_MODIFIED))
+    .map(str::to_string)
+}
+
+/// Bytes of the partial file to keep. A server that ignores the range sends the whole
+/// file again, so the partial file has to be overwritten.
+fn resume_offset(status: StatusCode, partial_len: u64) -> u64 {
+  if status == StatusCode::PARTIAL_CONTENT {
+    partial_len
+  } else {
+    0
+  }
+}
+
+/// Full size of the file from a `Content-Range` header, e.g. `bytes */1024` or
+/// `bytes 0-99/1024`. None when the server doesn't know it.
+fn content_range_total(content_range: &str) -> Option<u64> {
+  content_range
+    .strip_prefix("bytes ")?
+    .rsplit_once('/')?
+    .1
+    .trim()
+    .parse()
+    .ok()
 }
 
 /// Create all download items
@@ -464,3 +627,180 @@ pub fn check_setup_complete(app_handle: tauri::AppHandle) -> Result<bool, String
 
   Ok(true)
 }
+
+/// What a model file on disk is used for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "setup.ts")]
+pub enum ModelKind {
+  /// A GGUF model the user switched to
+  Llm,
+  VlmText,
+  VlmMmproj,
+  OcrDetection,
+  OcrRecognition,
+  Embedding,
+  EmbeddingTokenizer,
+}
+
+/// A model file that is present on disk
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "setup.ts")]
+pub struct ModelFile {
+  pub path: String,
+  pub kind: ModelKind,
+  pub size: u64,
+}
+
+/// Result of hashing a model file
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "setup.ts")]
+pub struct ModelVerification {
+  pub path: String,
+  pub size: u64,
+  pub sha256: String,
+  pub matches: bool,
+}
+
+/// List every downloaded model with its size, including a switched-to local model
+#[tauri::command]
+pub async fn list_models(app_handle: AppHandle) -> Result<Vec<ModelFile>, String> {
+  let app_data_path = app_handle
+    .path()
+    .app_data_dir()
+    .map_err(|e| format!("Could not resolve app data directory: {}", e))?;
+
+  let mut candidates = bundled_model_paths(&app_data_path);
+  let settings = crate::settings::service::load_user_settings(app_handle.clone()).await?;
+  if let Some(path) = settings.local_model_path {
+    candidates.insert(0, (ModelKind::Llm, PathBuf::from(path)));
+  }
+
+  Ok(present_models(candidates))
+}
+
+/// Hash a model file and compare it with the expected SHA-256, so a truncated or
+/// corrupted download can be told apart from other server start failures
+#[tauri::command]
+pub async fn verify_model(path: String, expected_sha256: String) -> Result<ModelVerification, String> {
+  log::info!("[setup] Verifying model {}", path);
+  // Models are several GB, keep the hashing off the async runtime
+  tokio::task::spawn_blocking(move || {
+    let (size, sha256) = hash_file(Path::new(&path))?;
+    let matches = sha256.eq_ignore_ascii_case(expected_sha256.trim());
+    if !matches {
+      log::warn!("[setup] Model {} does not match the expected checksum", path);
+    }
+    Ok(ModelVerification { path, size, sha256, matches })
+  })
+  .await
+  .map_err(|e| format!("Model verification task failed: {}", e))?
+}
+
+fn bundled_model_paths(app_data_path: &Path) -> Vec<(ModelKind, PathBuf)> {
+  let vlm_dir = app_data_path.join(VLM_DIR);
+  let ocr_dir = app_data_path.join(OCR_DIR);
+  let embedding_dir = app_data_path.join(EMBEDDING_DIR);
+  vec![
+    (ModelKind::VlmText, vlm_dir.join(TEXT_FILE)),
+    (ModelKind::VlmMmproj, vlm_dir.join(MMPROJ_FILE)),
+    (ModelKind::OcrDetection, ocr_dir.join(TEXT_DETECTION_FILE)),
+    (ModelKind::OcrRecognition, ocr_dir.join(TEXT_RECOGNITION_FILE)),
+    (ModelKind::Embedding, embedding_dir.join(EMBEDDING_FILE)),
+    (ModelKind::EmbeddingTokenizer, embedding_dir.join(EMBEDDING_TOKENIZER_FILE)),
+  ]
+}
+
+fn present_models(candidates: Vec<(ModelKind, PathBuf)>) -> Vec<ModelFile> {
+  candidates
+    .into_iter()
+    .filter_map(|(kind, path)| {
+      let metadata = fs::metadata(&path).ok().filter(|m| m.is_file())?;
+      Some(ModelFile {
+        path: path.to_string_lossy().to_string(),
+        kind,
+        size: metadata.len(),
+      })
+    })
+    .collect()
+}
+
+/// Stream a file through SHA-256, returning its size and lowercase hex digest
+fn hash_file(path: &Path) -> Result<(u64, String), String> {
+  let mut file = File::open(path)
+    .map_err(|e| format!("Failed to open model {}: {}", path.display(), e))?;
+  let mut hasher = Sha256::new();
+  let mut buffer = vec![0u8; 1024 * 1024];
+  let mut size: u64 = 0;
+  loop {
+    let read = file
+      .read(&mut buffer)
+      .map_err(|e| format!("Failed to read model {}: {}", path.display(), e))?;
+    if read == 0 {
+      break;
+    }
+    hasher.update(&buffer[..read]);
+    size += read as u64;
+  }
+  Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_lists_present_models_and_hashes_them() {
+    let root = std::env::temp_dir().join(format!("ambient-models-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(root.join(VLM_DIR)).unwrap();
+    fs::write(root.join(VLM_DIR).join(TEXT_FILE), b"abc").unwrap();
+
+    let models = present_models(bundled_model_paths(&root));
+    let hashed = hash_file(&root.join(VLM_DIR).join(TEXT_FILE));
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].kind, ModelKind::VlmText);
+    assert_eq!(models[0].size, 3);
+    assert_eq!(
+      hashed.unwrap(),
+      (3, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string())
+    );
+  }
+
+  #[test]
+  fn test_resumes_only_when_server_honours_range() {
+    assert_eq!(
+      partial_path(Path::new("/models/vlm/model.gguf")),
+      PathBuf::from("/models/vlm/model.gguf.part")
+    );
+    assert_eq!(resume_offset(StatusCode::PARTIAL_CONTENT, 1024), 1024);
+    assert_eq!(resume_offset(StatusCode::OK, 1024), 0);
+
+    // A 416 for a finished part file reports the size it already has
+    assert_eq!(content_range_total("bytes */1024"), Some(1024));
+    assert_eq!(content_range_total("bytes 0-99/1024"), Some(1024));
+    assert_eq!(content_range_total("bytes */*"), None);
+    assert_eq!(content_range_total("items */1024"), None);
+  }
+
+  #[test]
+  fn test_resume_validator_prefers_strong_etag() {
+    let modified = "Wed, 21 Oct 2026 07:28:00 GMT";
+    let mut headers = HeaderMap::new();
+    assert_eq!(resume_validator(&headers), None);
+
+    headers.insert(LAST_MODIFIED, modified.parse().unwrap());
+    assert_eq!(resume_validator(&headers).as_deref(), Some(modified));
+
+    headers.insert(ETAG, "W/\"abc\"".parse().unwrap());
+    assert_eq!(resume_validator(&headers).as_deref(), Some(modified));
+
+    headers.insert(ETAG, "\"abc\"".parse().unwrap());
+    assert_eq!(resume_validator(&headers).as_deref(), Some("\"abc\""));
+    assert_eq!(
+      validator_path(Path::new("/models/vlm/model.gguf")),
+      PathBuf::from("/models/vlm/model.gguf.part.validator")
+    );
+  }
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+use ts_rs::TS;
+
+/// Bytes on disk used by each category of app data
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "storage.ts")]
+pub struct StorageBreakdown {
+  pub database: u64,
+  pub attachments: u64,
+  pub models: u64,
+  pub logs: u64,
+  pub total: u64,
+}
+
+/// Report how much disk space the database, attachments, models and logs use
+#[tauri::command]
+pub fn get_storage_breakdown(app_handle: AppHandle) -> Result<StorageBreakdown, String> {
+  let app_data_dir = app_handle
+    .path()
+    .app_data_dir()
+    .map_err(|e| format!("Could not resolve app data directory: {}", e))?;
+  let log_dir = app_handle
+    .path()
+    .app_log_dir()
+    .map_err(|e| format!("Could not resolve app log directory: {}", e))?;
+
+  let db_path = crate::db::core::get_db_path(&app_handle)?;
+
+  Ok(compute_breakdown(
+    &db_path,
+    &app_data_dir.join("attachments"),
+    &app_data_dir.join("models"),
+    &log_dir,
+  ))
+}
+
+fn compute_breakdown(
+  db_path: &Path,
+  attachments_dir: &Path,
+  models_dir: &Path,
+  logs_dir: &Path,
+) -> StorageBreakdown {
+  // Include SQLite's write-ahead log and shared memory files alongside the database
+  let database = crate::db::core::database_files(db_path)
+    .iter()
+    .map(|path| file_size(path))
+    .sum();
+  let attachments = dir_size(attachments_dir);
+  let models = dir_size(models_dir);
+  let logs = dir_size(logs_dir);
+
+  StorageBreakdown {
+    database,
+    attachments,
+    models,
+    logs,
+    total: database + attachments + models + logs,
+  }
+}
+
+fn file_size(path: &Path) -> u64 {
+  fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Recursively sum file sizes under a directory; missing directories count as empty
+pub(crate) fn dir_size(dir: &Path) -> u64 {
+  let Ok(entries) = fs::read_dir(dir) else {
+    return 0;
+  };
+  entries
+    .flatten()
+    .map(|entry| match entry.file_type() {
+      Ok(t) if t.is_dir() => dir_size(&entry.path()),
+      Ok(t) if t.is_file() => file_size(&entry.path()),
+      _ => 0,
+    })
+    .sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_breakdown_sums_each_category() {
+    let root = std::env::temp_dir().join(format!("ambient-storage-{}", uuid::Uuid::new_v4()));
+    let attachments = root.join("attachments");
+    let models = root.join("models");
+    let logs = root.join("logs");
+    fs::create_dir_all(attachments.join("msg-1")).unwrap();
+    fs::create_dir_all(models.join("vlm")).unwrap();
+    fs::create_dir_all(&logs).unwrap();
+
+    fs::write(root.join("database.sqlite"), vec![0u8; 100]).unwrap();
+    fs::write(root.join("database.sqlite-wal"), vec![0u8; 20]).unwrap();
+    fs::write(attachments.join("msg-1").join("a.png"), vec![0u8; 30]).unwrap();
+    fs::write(attachments.join("msg-1").join("b.pdf"), vec![0u8; 12]).unwrap();
+    fs::write(models.join("vlm").join("model.gguf"), vec![0u8; 500]).unwrap();
+    fs::write(logs.join("logs.log"), vec![0u8; 7]).unwrap();
+
+    let breakdown = compute_breakdown(&root.join("database.sqlite"), &attachments, &models, &logs);
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(
+      breakdown,
+      StorageBreakdown {
+        database: 120,
+        attachments: 42,
+        models: 500,
+        logs: 7,
+        total: 669,
+      }
+    );
+  }
+}
@@ -1,3 +1,4 @@
+use crate::constants::SHUTDOWN_GRACE_PERIOD;
 use crate::models;
 use crate::windows;
 use image::GenericImageView;
@@ -67,12 +68,8 @@ pub fn create_tray(app: &AppHandle) -> tauri::Result<()> {
             log::info!("[tray] Quit requested from tray");
             let app_handle = app_handle.clone();
             tauri::async_runtime::spawn(async move {
-              // Stop the llama server gracefully
-              if let Err(e) = models::llm::server::stop_llama_server().await {
-                log::error!("[tray] Failed to stop llama server during quit: {}", e);
-              } else {
-                log::info!("[tray] Llama server stopped successfully");
-              }
+              // Let in-flight responses save, then stop the llama server
+              models::llm::shutdown::shutdown(SHUTDOWN_GRACE_PERIOD).await;
 
               // Exit the application
               app_handle.exit(0);
@@ -15,6 +15,7 @@ import type React from "react";
 import { useCallback, useState } from "react";
 import {
   AssistantMessage,
+  ContextBoundaryMessage,
   FunctionMessage,
   UserMessage,
 } from "./message-types";
@@ -123,6 +124,7 @@ export function MessageList({
               const role = m.message.role.toLowerCase();
               const isUser = role === "user";
               const isAssistant = role === "assistant";
+              const isBoundary = role === "contextboundary";
 
               return (
                 <div
@@ -146,6 +148,8 @@ export function MessageList({
                       toggleReasoning={toggleReasoning}
                       showReasoning={showReasoning.has(i)}
                     />
+                  ) : isBoundary ? (
+                    <ContextBoundaryMessage m={m} />
                   ) : (
                     <FunctionMessage m={m} />
                   )}
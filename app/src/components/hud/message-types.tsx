@@ -370,3 +370,13 @@ export function FunctionMessage({ m }: { m: ChatMessage }) {
     </div>
   );
 }
+
+export function ContextBoundaryMessage({ m }: { m: ChatMessage }) {
+  return (
+    <div className="flex items-center gap-2 mt-6 text-xs text-white/70">
+      <div className="h-px flex-1 bg-white/30" />
+      {m.message.content}
+      <div className="h-px flex-1 bg-white/30" />
+    </div>
+  );
+}
@@ -34,6 +34,11 @@ const MODEL_OPTIONS = [
     label: "Gemini 3 Pro",
     description: "The latest and most advanced model.",
   },
+  {
+    value: "Ollama",
+    label: "Ollama",
+    description: "Your own models through a local Ollama server.",
+  },
 ] as const;
 
 export function ModelSelector({ onOpenChange, disabled }: ModelSelectorProps) {
@@ -39,6 +39,10 @@ const chartConfig = {
     label: "Pro",
     color: "#2563eb",
   },
+  ollama: {
+    label: "Ollama",
+    color: "#64748b",
+  },
   "computer-use": {
     label: "Computer Use",
     color: "#f59e0b",
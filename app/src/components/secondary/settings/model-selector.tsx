@@ -13,7 +13,7 @@ import {
   SelectValue,
 } from "@/components/ui/select";
 import type { ModelSelection } from "@/types/settings";
-import { Crown, Shield, Zap } from "lucide-react";
+import { Crown, Server, Shield, Zap } from "lucide-react";
 
 interface ModelConfig {
   value: ModelSelection;
@@ -60,6 +60,15 @@ const MODEL_CONFIGS: ModelConfig[] = [
     },
     upgradeButton: true,
   },
+  {
+    value: "Ollama",
+    name: "Ollama",
+    description:
+      "Bring your own model. Runs through an Ollama server you control.",
+    icon: <Server className="h-4 w-4 m-1.5 text-slate-600" />,
+    iconBgClass: "bg-slate-100",
+    badge: { label: "Custom", variant: "outline" },
+  },
 ];
 
 interface ModelSelectorProps {
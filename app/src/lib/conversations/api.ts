@@ -1,4 +1,4 @@
-import type { Conversation } from "@/types/conversations";
+import type { Conversation, Message } from "@/types/conversations";
 import {
   type AttachmentData,
   type GenerateConversationNameEvent,
@@ -139,6 +139,72 @@ export async function deleteConversation(
   }
 }
 
+/**
+ * Replaces the content of a user message
+ * @param messageId - ID of the message to edit
+ * @param newContent - New message text
+ */
+export async function editMessage(
+  messageId: string,
+  newContent: string,
+): Promise<void> {
+  try {
+    await invoke("edit_message", { messageId, newContent });
+  } catch (error) {
+    console.error("[ConversationAPI] Failed to edit message:", error);
+    throw new Error("Failed to edit message");
+  }
+}
+
+/**
+ * Deletes every message after a user message and generates a new response
+ * @param messageId - ID of the user message to answer again
+ */
+export async function regenerateFrom(messageId: string): Promise<string> {
+  try {
+    return await invoke<string>("regenerate_from", { messageId });
+  } catch (error) {
+    console.error("[ConversationAPI] Failed to regenerate response:", error);
+    throw new Error("Failed to regenerate response");
+  }
+}
+
+/**
+ * Copies a conversation up to and including a message into a new conversation
+ * @param conversationId - ID of the conversation to fork
+ * @param fromMessageId - ID of the last message to copy
+ * @returns Promise resolving to the new Conversation
+ */
+export async function forkConversation(
+  conversationId: string,
+  fromMessageId: string,
+): Promise<Conversation> {
+  try {
+    return await invoke<Conversation>("fork_conversation", {
+      conversationId,
+      fromMessageId,
+    });
+  } catch (error) {
+    console.error("[ConversationAPI] Failed to fork conversation:", error);
+    throw new Error("Failed to fork conversation");
+  }
+}
+
+/**
+ * Starts a fresh context window, keeping earlier messages visible
+ * @param conversationId - ID of the conversation
+ */
+export async function insertContextBoundary(
+  conversationId: string,
+): Promise<Message> {
+  try {
+    return await invoke<Message>("insert_context_boundary", { conversationId });
+  } catch (error) {
+    console.error("[ConversationAPI] Failed to insert context boundary:", error);
+    throw new Error("Failed to reset conversation context");
+  }
+}
+
 /**
  * Ensures the llama server is running
  */
@@ -147,6 +147,26 @@ export function SettingsProvider({ children }: SettingsProviderProps) {
         const defaults: UserSettings = {
           hud_size: "Normal",
           model_selection: "Local",
+          llm_debug_logging: false,
+          server_launch: {
+            ctx_size: 32768,
+            parallel_sequences: 3,
+            kv_cache_quant: "q8_0",
+            mlock: true,
+            flash_attention: true,
+            extra_args: [],
+          },
+          computer_use_autonomous: false,
+          model_prices: [
+            { model: "fast", prompt_per_million: 0.5, completion_per_million: 3.0 },
+            { model: "pro", prompt_per_million: 2.0, completion_per_million: 12.0 },
+            {
+              model: "computer-use",
+              prompt_per_million: 1.25,
+              completion_per_million: 10.0,
+            },
+          ],
+          provider_fallbacks: [],
         };
         dispatch({ type: "SET_SETTINGS", payload: defaults });
       }
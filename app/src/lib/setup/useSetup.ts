@@ -1,5 +1,6 @@
 "use client";
 
+import type { ModelFile, ModelVerification } from "@/types/setup";
 import { invoke } from "@tauri-apps/api/core";
 import { useCallback, useMemo } from "react";
 import { formatBytes } from "../utils";
@@ -30,6 +31,20 @@ export function useSetup() {
     }
   }, [dispatch]);
 
+  const listModels = useCallback(async () => {
+    return await invoke<ModelFile[]>("list_models");
+  }, []);
+
+  const verifyModel = useCallback(
+    async (path: string, expectedSha256: string) => {
+      return await invoke<ModelVerification>("verify_model", {
+        path,
+        expectedSha256,
+      });
+    },
+    [],
+  );
+
   const totalDownloadedBytes = useMemo(() => {
     return state.downloadedBytes.reduce((a, b) => a + b, 0);
   }, [state.downloadedBytes]);
@@ -55,5 +70,7 @@ export function useSetup() {
 
     // Operations
     startSetup,
+    listModels,
+    verifyModel,
   };
 }
@@ -54,6 +54,11 @@ delivery_medium: string | null, };
  */
 export type AuthState = { is_online: boolean, is_authenticated: boolean, is_setup_complete: boolean, user: UserInfo | null, needs_refresh: boolean, expires_at: bigint | null, };
 
+/**
+ * Kinds of credentials the app may hold on this device
+ */
+export type CredentialKind = "supabase_session" | "storage_encryption_key";
+
 /**
  * OAuth URL Response
  */
@@ -112,6 +117,15 @@ session: Session,
  */
 stored_at: bigint, };
 
+/**
+ * A stored credential, described without exposing any secret
+ */
+export type StoredCredential = { kind: CredentialKind, 
+/**
+ * Expiry as a Unix timestamp, when known
+ */
+expires_at: bigint | null, };
+
 /**
  * Complete Supabase User object
  */
@@ -9,11 +9,20 @@ export type Attachment = { id: string, message_id: string, file_type: string, fi
 /**
  * Conversation structure
  */
-export type Conversation = { id: string, name: string, conv_type: string, created_at: string, updated_at: string, message_count: number, };
+export type Conversation = { id: string, name: string, conv_type: string, created_at: string, updated_at: string, message_count: number, is_pinned: boolean, is_archived: boolean, };
 
 /**
  * Message structure
  */
 export type Message = { id: string, conversation_id: string, role: Role, content: string, timestamp: string, attachments: Array<Attachment>, memory: MemoryEntry | null, };
 
-export type Role = "system" | "user" | "assistant" | "functioncall";
+/**
+ * A message matching a full-text search, with a highlighted excerpt
+ */
+export type MessageSearchResult = { 
+/**
+ * Attachments and memory are not loaded for search results
+ */
+message: Message, snippet: string, };
+
+export type Role = "system" | "user" | "assistant" | "functioncall" | "contextboundary";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Error returned by database commands. Serialized as `{ kind, message }` so the UI
+ * can tell a missing record from a busy database.
+ */
+export type DbError = { "kind": "NotFound", "message": string } | { "kind": "Locked" } | { "kind": "Constraint", "message": string } | { "kind": "Serialization", "message": string } | { "kind": "SchemaTooNew", "message": number } | { "kind": "Other", "message": string };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Embedding for one input of a batch. Exactly one of `embedding` and `error` is set.
+ */
+export type EmbeddingResult = { embedding: Array<number> | null, error: string | null, };
@@ -16,7 +16,11 @@ export type DownloadFinishedEvent = { id: bigint, };
 
 export type DownloadInformationEvent = { n_items: bigint, content_length: bigint, };
 
-export type DownloadProgressEvent = { id: bigint, total_progress: bigint, };
+export type DownloadProgressEvent = { id: bigint, total_progress: bigint, 
+/**
+ * Size of the file, when the server reports it
+ */
+total: bigint | null, bytes_per_second: bigint, };
 
 export type DownloadStartedEvent = { id: bigint, };
 
@@ -28,12 +32,28 @@ export type HudChatEvent = { text: string, timestamp: string, conv_id: string, m
 
 export type MemoryExtractedEvent = { memory: MemoryEntry, timestamp: string, };
 
+export type MessagesDeletedEvent = { conversation_id: string, message_ids: Array<string>, timestamp: string, };
+
+export type ModelSwitchedEvent = { 
+/**
+ * None when back on the bundled model
+ */
+model_path: string | null, timestamp: string, };
+
 export type OcrResponseEvent = { text: string, success: boolean, timestamp: string, };
 
+export type ProviderFallbackEvent = { conv_id: string | null, 
+/**
+ * Models that failed, in the order they were tried
+ */
+failed_models: Array<string>, answered_model: string, timestamp: string, };
+
 export type RenameConversationEvent = { conv_id: string, new_name: string, timestamp: string, };
 
 export type SafetyConfirmationEvent = { reason: string, timestamp: string, };
 
 export type SafetyConfirmationResponseEvent = { user_confirmed: boolean, timestamp: string, };
 
+export type ServerRestartedEvent = { restart_count: number, timestamp: string, };
+
 export type TokenUsageChangedEvent = { timestamp: string, };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A display that can be captured
+ */
+export type MonitorInfo = { index: number, x: number, y: number, width: number, height: number, scale_factor: number, is_primary: boolean, };
+
+/**
+ * Rectangle to capture, relative to the monitor's top-left corner
+ */
+export type ScreenRegion = { x: number, y: number, width: number, height: number, };
+
+/**
+ * Screenshot returned to the frontend, with the image as base64 PNG
+ */
+export type ScreenshotResponse = { monitor_index: number, region: ScreenRegion, image_base64: string, };
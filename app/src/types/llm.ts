@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ModelSelection } from "./settings";
+
+/**
+ * A raw request/response pair sent to an LLM provider
+ */
+export type LlmExchange = { conversation_id: string | null, provider: string, request: any, response: string, timestamp: string, };
+
+/**
+ * Side-by-side results of running one prompt through two models
+ */
+export type ModelComparison = { prompt: string, model_a: ModelRunResult, model_b: ModelRunResult, };
+
+/**
+ * Output and stats for a single model in a comparison
+ */
+export type ModelRunResult = { model: ModelSelection, response: string | null, error: string | null, duration_ms: bigint, prompt_tokens: bigint, completion_tokens: bigint, };
@@ -1,3 +1,21 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
 export type MemoryEntry = { id: string, message_id: string, memory_type: string, text: string, embedding: Array<number>, timestamp: string, similarity: number | null, };
+
+/**
+ * How `search_memories` trades similarity off against recency and memory type
+ */
+export type MemoryRankWeights = { 
+/**
+ * Weight of the recency term, which halves every `half_life_days`
+ */
+recency: number, half_life_days: number, 
+/**
+ * Added to the score of memories of each type, e.g. `{ "semantic": 0.1 }`
+ */
+type_boosts: { [key in string]?: number }, };
+
+/**
+ * A memory search result with the score it was ranked by
+ */
+export type RankedMemory = { memory: MemoryEntry, score: number, };
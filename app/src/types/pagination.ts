@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A single page of results along with the metadata needed to request the next one
+ */
+export type Paginated<T> = { items: Array<T>, total: bigint, offset: bigint, limit: bigint, has_more: boolean, };
@@ -9,6 +9,65 @@ export type HudSizeOption = "Small" | "Normal" | "Large";
 
 export type HudState = "Input" | "Chat" | "Login" | "Default";
 
-export type ModelSelection = "Local" | "Fast" | "Pro";
+/**
+ * Price of a cloud model in USD per million tokens
+ */
+export type ModelPrice = { model: string, prompt_per_million: number, completion_per_million: number, };
 
-export type UserSettings = { hud_size: HudSizeOption, model_selection: ModelSelection, };
+export type ModelSelection = "Local" | "Fast" | "Pro" | "Ollama";
+
+/**
+ * Launch flags for the local llama.cpp server
+ */
+export type ServerLaunchConfig = { ctx_size: number, parallel_sequences: number, 
+/**
+ * Quantization for the KV cache, e.g. "q8_0" or "f16"
+ */
+kv_cache_quant: string, mlock: boolean, flash_attention: boolean, 
+/**
+ * Passed to the server as-is, after the built-in flags. Flags Ambient manages,
+ * such as --port and --api-key, are rejected.
+ */
+extra_args: Array<string>, };
+
+export type UserSettings = { hud_size: HudSizeOption, model_selection: ModelSelection, 
+/**
+ * Overrides the local model's embedded chat template when set
+ */
+chat_template_path?: string, 
+/**
+ * Persist raw provider requests and responses for debugging
+ */
+llm_debug_logging: boolean, 
+/**
+ * Base URL of the Ollama server, defaults to localhost
+ */
+ollama_base_url?: string, 
+/**
+ * Ollama model name to generate with
+ */
+ollama_model?: string, 
+/**
+ * GGUF model served instead of the bundled one, set by switching models
+ */
+local_model_path?: string, 
+/**
+ * Vision projector for `local_model_path`. Without one the switched model is text-only.
+ */
+local_mmproj_path?: string, 
+/**
+ * Takes effect the next time the local server starts
+ */
+server_launch: ServerLaunchConfig, 
+/**
+ * Let computer use act without asking before each action
+ */
+computer_use_autonomous: boolean, 
+/**
+ * Used to estimate the cost of cloud usage per conversation
+ */
+model_prices: Array<ModelPrice>, 
+/**
+ * Models to try in order when the selected one fails. Empty turns failover off.
+ */
+provider_fallbacks: Array<ModelSelection>, };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A model file that is present on disk
+ */
+export type ModelFile = { path: string, kind: ModelKind, size: bigint, };
+
+/**
+ * What a model file on disk is used for
+ */
+export type ModelKind = "llm" | "vlm_text" | "vlm_mmproj" | "ocr_detection" | "ocr_recognition" | "embedding" | "embedding_tokenizer";
+
+/**
+ * Result of hashing a model file
+ */
+export type ModelVerification = { path: string, size: bigint, sha256: string, matches: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Bytes on disk used by each category of app data
+ */
+export type StorageBreakdown = { database: bigint, attachments: bigint, models: bigint, logs: bigint, total: bigint, };
@@ -2,6 +2,17 @@
 
 export type AggregationLevel = "Hour" | "Day" | "Week" | "Month";
 
+/**
+ * Tokens one model used within a conversation
+ */
+export type ConversationModelUsage = { model: string, provider: string, prompt_tokens: bigint, completion_tokens: bigint, 
+/**
+ * Estimated USD cost, zero for models without a price such as local ones
+ */
+estimated_cost: number, };
+
+export type ConversationUsage = { conversation_id: string, prompt_tokens: bigint, completion_tokens: bigint, estimated_cost: number, models: Array<ConversationModelUsage>, };
+
 /**
  * Time filters for querying token usage
  */